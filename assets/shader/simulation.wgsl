@@ -1,8 +1,48 @@
 // Textures are stored in a double buffer.
 // The shader reads the input texture and writes to the output texture.
+// Texels are encoded cells, see Cell::encode in sim/grid.rs:
+// r = kind (0 empty, 1 active, 2 captured, 3 dying, 4 dormant), g = owner + 1 (0 = none), b = state.
+// Active and dying cells keep the owner of the stamp they grew from.
 @group(0) @binding(0) var input: texture_storage_2d<rgba8unorm, read>;
 @group(0) @binding(1) var output: texture_storage_2d<rgba8unorm, write>;
 
+// Mirrors SimParams in render/gpu/shader.rs.
+// table[condition * NUM_COUNTS + n_alive] holds the chance to become active (x)
+// and the chance to become empty (y). The remainder leaves the cell untouched.
+struct SimParams {
+    table: array<vec4<f32>, 196>,
+    team: u32,
+    generation: u32,
+    seed: u32,
+    // BoundaryMode: 0 dead, 1 torus, 2 mirror, 3 wall.
+    boundary: u32,
+    // Neighborhood::kind: 0 moore, 1 von neumann, 2 hex.
+    neighborhood: u32,
+    radius: u32,
+    stages: u32,
+}
+@group(0) @binding(2) var<uniform> params: SimParams;
+
+// Mirrors NUM_COUNTS in sim/rules.rs.
+const NUM_COUNTS: u32 = 49u;
+const KIND_EMPTY: u32 = 0u;
+const KIND_ACTIVE: u32 = 1u;
+const KIND_DYING: u32 = 3u;
+const KIND_DORMANT: u32 = 4u;
+// Mirrors MAX_OWNERS in sim/render/cpu.rs.
+const MAX_OWNERS: u32 = 4u;
+const EMPTY_CELL: vec4<f32> = vec4<f32>(0.0, 0.0, 0.0, 1.0);
+const ACTIVE_CELL: vec4<f32> = vec4<f32>(1.0 / 255.0, 0.0, 0.0, 1.0);
+
+// `owner` is encoded like the g channel: team + 1, or 0 for none.
+fn active_cell(owner: u32) -> vec4<f32> {
+    return vec4<f32>(f32(KIND_ACTIVE) / 255.0, f32(owner) / 255.0, 0.0, 1.0);
+}
+
+fn dying_cell(stage: u32, owner: u32) -> vec4<f32> {
+    return vec4<f32>(f32(KIND_DYING) / 255.0, f32(owner) / 255.0, f32(stage) / 255.0, 1.0);
+}
+
 
 fn hash(value: u32) -> u32 {
     var state = value;
@@ -15,53 +55,165 @@ fn hash(value: u32) -> u32 {
     return state;
 }
 
+// Uniform in [0, 1). Mirrors random_float in sim/random.rs.
 fn randomFloat(value: u32) -> f32 {
-    return f32(hash(value)) / 4294967295.0;
+    return f32(hash(value) >> 8u) / 16777216.0;
 }
 
-@compute @workgroup_size(8, 8, 1)
-fn init(@builtin(global_invocation_id) invocation_id: vec3<u32>, @builtin(num_workgroups) num_workgroups: vec3<u32>) {
-    let location = vec2<i32>(i32(invocation_id.x), i32(invocation_id.y));
+fn cellId(invocation_id: vec3<u32>) -> u32 {
+    return (invocation_id.y << 16u) | invocation_id.x;
+}
 
-    let randomNumber = randomFloat((invocation_id.y << 16u) | invocation_id.x);
-    let alive = randomNumber > 0.9;
-    let color = vec4<f32>(f32(alive));
+fn unpack(value: f32) -> u32 {
+    return u32(round(value * 255.0));
+}
 
-    textureStore(output, location, color);
+fn reflect_coord(c: i32, n: i32) -> i32 {
+    let period = 2 * n;
+    let m = ((c % period) + period) % period;
+    return select(period - m - 1, m, m < n);
 }
 
-fn is_alive(location: vec2<i32>, offset_x: i32, offset_y: i32) -> i32 {
-    let value: vec4<f32> = textureLoad(input, location + vec2<i32>(offset_x, offset_y));
-    return i32(value.x);
+// Loads a cell, resolving positions past the edge like BoundaryMode::resolve.
+fn load(location: vec2<i32>) -> vec4<f32> {
+    let size = vec2<i32>(textureDimensions(input));
+    if (all(location >= vec2<i32>(0)) && all(location < size)) {
+        return textureLoad(input, location);
+    }
+    switch params.boundary {
+        case 1u: {
+            return textureLoad(input, ((location % size) + size) % size);
+        }
+        case 2u: {
+            return textureLoad(input, vec2<i32>(reflect_coord(location.x, size.x), reflect_coord(location.y, size.y)));
+        }
+        case 3u: {
+            return ACTIVE_CELL;
+        }
+        default: {
+            return EMPTY_CELL;
+        }
+    }
 }
 
-fn count_alive(location: vec2<i32>) -> i32 {
-    return is_alive(location, -1, -1) +
-           is_alive(location, -1,  0) +
-           is_alive(location, -1,  1) +
-           is_alive(location,  0, -1) +
-           is_alive(location,  0,  1) +
-           is_alive(location,  1, -1) +
-           is_alive(location,  1,  0) +
-           is_alive(location,  1,  1);
+// Matches CellCondition: Empty, Active, Owned, Enemy.
+fn condition(value: vec4<f32>) -> u32 {
+    let kind = unpack(value.r);
+    if (kind == KIND_EMPTY) {
+        return 0u;
+    } else if (kind == KIND_ACTIVE) {
+        return 1u;
+    } else if (unpack(value.g) == params.team + 1u) {
+        return 2u;
+    }
+    return 3u;
+}
+
+// Mirrors Neighborhood::contains in sim/neighborhood.rs.
+fn in_neighborhood(x: i32, y: i32) -> bool {
+    if (x == 0 && y == 0) {
+        return false;
+    }
+    switch params.neighborhood {
+        case 1u: {
+            return abs(x) + abs(y) <= i32(params.radius);
+        }
+        case 2u: {
+            return x * y != -1;
+        }
+        default: {
+            return true;
+        }
+    }
+}
+
+struct Neighbors {
+    n_alive: u32,
+    // Encoded owner for a cell born here, see birth_owner in sim/render/cpu.rs:
+    // the team owning most active neighbors, the lowest on a tie.
+    owner: u32,
+}
+
+fn count_alive(location: vec2<i32>) -> Neighbors {
+    let r = i32(params.radius);
+    var n_alive = 0u;
+    var owners = array<u32, MAX_OWNERS>(0u, 0u, 0u, 0u);
+    for (var y = -r; y <= r; y++) {
+        for (var x = -r; x <= r; x++) {
+            if (!in_neighborhood(x, y)) {
+                continue;
+            }
+            let value = load(location + vec2<i32>(x, y));
+            if (unpack(value.r) != KIND_ACTIVE) {
+                continue;
+            }
+            n_alive += 1u;
+            let owner = unpack(value.g);
+            if (owner > 0u && owner <= MAX_OWNERS) {
+                owners[owner - 1u] += 1u;
+            }
+        }
+    }
+    var best = 0u;
+    var best_count = 0u;
+    for (var team = 0u; team < MAX_OWNERS; team++) {
+        if (owners[team] > best_count) {
+            best = team + 1u;
+            best_count = owners[team];
+        }
+    }
+    return Neighbors(n_alive, best);
 }
 
 @compute @workgroup_size(8, 8, 1)
 fn update(@builtin(global_invocation_id) invocation_id: vec3<u32>) {
     let location = vec2<i32>(i32(invocation_id.x), i32(invocation_id.y));
+    if (any(location >= vec2<i32>(textureDimensions(input)))) {
+        return;
+    }
+
+    let current = textureLoad(input, location);
+    let kind = unpack(current.r);
+
+    // Dormant seeds are woken on the CPU, see seeds.rs.
+    if (kind == KIND_DORMANT) {
+        textureStore(output, location, current);
+        return;
+    }
+
+    // Dying cells ignore their neighbors and decay one stage per step.
+    if (kind == KIND_DYING) {
+        let stage = unpack(current.b);
+        if (stage >= params.stages) {
+            textureStore(output, location, EMPTY_CELL);
+        } else {
+            textureStore(output, location, dying_cell(stage + 1u, unpack(current.g)));
+        }
+        return;
+    }
+
+    let neighbors = count_alive(location);
+    let transition = params.table[condition(current) * NUM_COUNTS + neighbors.n_alive];
 
-    let n_alive = count_alive(location);
+    let roll = randomFloat(cellId(invocation_id) ^ hash(params.generation ^ hash(params.seed)));
 
-    var alive: bool;
-    if (n_alive == 3) {
-        alive = true;
-    } else if (n_alive == 2) {
-        let currently_alive = is_alive(location, 0, 0);
-        alive = bool(currently_alive);
+    var cell: vec4<f32>;
+    if (roll < transition.x) {
+        if (kind == KIND_ACTIVE) {
+            cell = current;
+        } else {
+            cell = active_cell(neighbors.owner);
+        }
+    } else if (roll < transition.x + transition.y) {
+        if (kind == KIND_ACTIVE && params.stages > 0u) {
+            cell = dying_cell(1u, unpack(current.g));
+        } else {
+            cell = EMPTY_CELL;
+        }
     } else {
-        alive = false;
+        // untouched
+        cell = current;
     }
-    let color = vec4<f32>(f32(alive));
 
-    textureStore(output, location, color);
+    textureStore(output, location, cell);
 }
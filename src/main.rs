@@ -10,6 +10,8 @@ pub(crate) use plugins::*;
 fn main() -> AppExit {
     let mut app = App::new();
 
+    // Asset sources have to be in place before the asset plugin is added.
+    stamps::register_library_source(&mut app);
     app.add_plugins((
         DefaultPlugins.set(ImagePlugin::default_nearest()),
         SimpleSubsecondPlugin::default(),
@@ -1,6 +1,10 @@
 use bevy::{prelude::*, render::extract_resource::ExtractResource};
 use derivative::Derivative;
 
+use crate::sim::{
+    Cell, CellKind, Neighborhood, SimSeed, TransitionTable, TurnOrder, VictoryCondition, WakeMask,
+};
+
 /// Index into team vec
 pub type TeamID = usize;
 /// Index into player vec
@@ -19,6 +23,36 @@ pub struct Player {
     pub name: String,
 }
 
+/// Two single-player teams, used until a match is set up.
+fn default_teams() -> Vec<Team> {
+    vec![
+        Team {
+            id: 0,
+            name: "A".into(),
+            players: vec![0],
+            color: [255, 0, 0, 255],
+        },
+        Team {
+            id: 1,
+            name: "B".into(),
+            players: vec![1],
+            color: [0, 0, 255, 255],
+        },
+    ]
+}
+fn default_players() -> Vec<Player> {
+    vec![
+        Player {
+            name: "Player 1".into(),
+            team: 0,
+        },
+        Player {
+            name: "Player 2".into(),
+            team: 1,
+        },
+    ]
+}
+
 /// Used to check cell state
 #[derive(Debug, PartialEq, Eq, Copy, Clone, Default)]
 pub enum CellCondition {
@@ -27,19 +61,30 @@ pub enum CellCondition {
     Active,
     Owned,
     Enemy,
+    /// Decaying after death, counting up from stage 1.
+    Dying(u8),
+    /// A seed waiting to wake. Never passed to a rule as the current cell.
+    Dormant,
 }
 #[derive(Debug, PartialEq, Eq, Copy, Clone)]
 pub enum CellResult {
     Empty,
     Active,
     Untouched,
+    /// Enter the given decay stage.
+    Dying(u8),
 }
 
-/// Double buffer.
+/// `texture_a` and `texture_b` are the compute shader's double buffer of
+/// encoded cells. The last state written is copied to `texture_out`, which is
+/// read back. The display and preview textures are colorized from
+/// [`crate::sim::CellGrid`] and [`crate::sim::PreviewGrid`].
 #[derive(Debug, Resource, Clone, ExtractResource, Default)]
 pub struct SimImages {
     pub texture_a: Handle<Image>,
     pub texture_b: Handle<Image>,
+    pub texture_out: Handle<Image>,
+    pub display_texture: Handle<Image>,
     pub preview_texture: Handle<Image>,
 }
 
@@ -50,6 +95,8 @@ pub struct SimSprite;
 pub type PixelColor<'a> = &'a [u8; 4];
 pub const BLACK: PixelColor = &[0, 0, 0, 255];
 pub const WHITE: PixelColor = &[255, 255, 255, 255];
+/// Empty cells inside a king of the hill zone.
+pub const HILL: PixelColor = &[72, 56, 0, 255];
 
 #[derive(Default, Debug, strum::Display, Copy, Clone, PartialEq)]
 pub enum SimLayout {
@@ -77,8 +124,62 @@ impl TryFrom<&String> for SimLayout {
     }
 }
 
-#[derive(Event)]
-pub struct StampEvent;
+/// What the cells past the edge of the board look like to their neighbors.
+/// The discriminant is what the compute shader receives.
+#[derive(Default, Debug, strum::Display, Copy, Clone, PartialEq, Eq)]
+pub enum BoundaryMode {
+    /// Everything past the edge is empty.
+    #[default]
+    Dead = 0,
+    /// The board wraps around on both axes.
+    Torus = 1,
+    /// The edge reflects the board, so the border cells see themselves.
+    Mirror = 2,
+    /// Everything past the edge is active. The border behaves like a fixed wall.
+    Wall = 3,
+}
+impl BoundaryMode {
+    /// The in-range position `pos` reads from, or `None` if it reads the
+    /// fixed edge cell.
+    pub fn resolve(&self, pos: IVec2, size: UVec2) -> Option<UVec2> {
+        let size = size.as_ivec2();
+        if pos.cmpge(IVec2::ZERO).all() && pos.cmplt(size).all() {
+            return Some(pos.as_uvec2());
+        }
+        match self {
+            Self::Torus => Some(pos.rem_euclid(size).as_uvec2()),
+            Self::Mirror => {
+                let reflect = |c: i32, n: i32| {
+                    let c = c.rem_euclid(2 * n);
+                    if c < n { c } else { 2 * n - c - 1 }
+                };
+                Some(UVec2::new(
+                    reflect(pos.x, size.x) as u32,
+                    reflect(pos.y, size.y) as u32,
+                ))
+            }
+            Self::Dead | Self::Wall => None,
+        }
+    }
+}
+impl TryFrom<&String> for BoundaryMode {
+    type Error = anyhow::Error;
+    fn try_from(value: &String) -> anyhow::Result<Self> {
+        match value.as_str() {
+            "Dead" => Ok(Self::Dead),
+            "Torus" => Ok(Self::Torus),
+            "Mirror" => Ok(Self::Mirror),
+            "Wall" => Ok(Self::Wall),
+            _ => Err(anyhow::anyhow!("No such boundary mode")),
+        }
+    }
+}
+
+/// The board was clicked at `position`, in grid coordinates.
+#[derive(Event, Debug, Copy, Clone)]
+pub struct StampEvent {
+    pub position: Vec2,
+}
 
 #[derive(States, Default, Debug, PartialEq, Eq, Hash, Copy, Clone)]
 pub enum SimState {
@@ -89,18 +190,110 @@ pub enum SimState {
     Running,
 }
 
-#[derive(Resource, Debug, Default)]
+#[derive(Resource, Clone, Debug, Default, ExtractResource)]
 pub struct SimGameplayState {
     pub current_stamp: Option<String>,
     pub num_steps: u32,
+    /// Steps the current turn runs for. Starts at `steps_per_turn` and is
+    /// pushed back whenever a seed wakes, see `extend_turn`.
+    pub step_limit: u32,
+    /// Steps taken so far. Unlike `num_steps` this is not reset between turns.
+    pub generation: u32,
     pub current_player: PlayerID,
+    /// Turns finished so far, counting every player's turn.
+    pub turn: u32,
+    /// Whether a match is being played, as opposed to the sandbox. Victory
+    /// conditions only apply to matches.
+    pub in_match: bool,
+    /// Turns in the match, set from the drafted loadouts once it starts.
+    pub total_turns: u32,
+    /// Freezes the hover preview, e.g. while a placement waits for confirmation.
+    pub preview_locked: bool,
+    /// Plants stamps as dormant seeds with this mask, unless empty.
+    pub wake_mask: WakeMask,
+    /// Seed money left per player. Empty means stamps are free, as in the sandbox.
+    pub wallets: Vec<u32>,
+    /// Stamps each player drafted, one entry per seed. Empty when nothing
+    /// was drafted, in which case stamps are paid for on placement.
+    pub loadouts: Vec<Vec<String>>,
+}
+impl SimGameplayState {
+    /// The team the sim runs for. Owned and enemy territory are relative to it.
+    /// A simultaneous round belongs to nobody, so all territory counts as enemy
+    /// and the round runs [`crate::sim::SimRules::default`], ignoring per-team
+    /// rules.
+    pub fn sim_team(&self, settings: &SimSettings) -> TeamID {
+        if settings.turn_order == TurnOrder::Simultaneous {
+            settings.teams.len()
+        } else {
+            settings.players[self.current_player].team
+        }
+    }
+    pub fn remaining_steps(&self) -> u32 {
+        self.step_limit.saturating_sub(self.num_steps)
+    }
+    /// Money left to the current player, if stamps cost anything.
+    pub fn balance(&self) -> Option<u32> {
+        self.wallets.get(self.current_player).copied()
+    }
+    pub fn can_afford(&self, cost: u32) -> bool {
+        self.balance().is_none_or(|balance| balance >= cost)
+    }
+    /// Takes `cost` from the current player's wallet. Fails without
+    /// charging anything if they can't afford it.
+    pub fn spend(&mut self, cost: u32) -> bool {
+        if !self.can_afford(cost) {
+            return false;
+        }
+        if let Some(balance) = self.wallets.get_mut(self.current_player) {
+            *balance -= cost;
+        }
+        true
+    }
+    /// Scaling a stamp by `scale` covers `scale²` times the cells, so it
+    /// costs as much more.
+    pub fn scaled_cost(cost: u32, scale: u8) -> u32 {
+        cost.saturating_mul((scale.max(1) as u32).pow(2))
+    }
+    /// Whether the current player may place the stamp `name` at `scale`.
+    /// A drafted seed is paid for at its base cost, so scaling it up takes
+    /// the difference out of the wallet.
+    pub fn can_place(&self, name: &str, cost: u32, scale: u8) -> bool {
+        let scaled = Self::scaled_cost(cost, scale);
+        match self.loadouts.get(self.current_player) {
+            Some(loadout) => {
+                loadout.iter().any(|seed| seed == name) && self.can_afford(scaled - cost)
+            }
+            None => self.can_afford(scaled),
+        }
+    }
+    /// Takes the stamp out of the current player's loadout, or pays for it
+    /// if there is no loadout, and pays for its scale. Fails without
+    /// charging anything if the player can't place it.
+    pub fn use_stamp(&mut self, name: &str, cost: u32, scale: u8) -> bool {
+        if !self.can_place(name, cost, scale) {
+            return false;
+        }
+        let scaled = Self::scaled_cost(cost, scale);
+        match self.loadouts.get_mut(self.current_player) {
+            Some(loadout) => {
+                if let Some(index) = loadout.iter().position(|seed| seed == name) {
+                    loadout.remove(index);
+                }
+                self.spend(scaled - cost)
+            }
+            None => self.spend(scaled),
+        }
+    }
 }
 
 // Intialized through the UI.
 #[derive(Resource, Clone, Debug, PartialEq, Derivative, ExtractResource)]
 #[derivative(Default)]
 pub struct SimSettings {
+    #[derivative(Default(value = "default_teams()"))]
     pub teams: Vec<Team>,
+    #[derivative(Default(value = "default_players()"))]
     pub players: Vec<Player>,
     pub parent_node: Option<Entity>,
     #[derivative(Default(value = "32"))]
@@ -109,23 +302,57 @@ pub struct SimSettings {
     pub timestep: u32, // fps
     #[derivative(Default(value = "10"))]
     pub steps_per_turn: u32,
+    /// Seed activations keep a turn going, but never past this many steps.
+    #[derivative(Default(value = "100"))]
+    pub max_steps_per_turn: u32,
+    /// Each player's budget for buying seeds.
+    #[derivative(Default(value = "100"))]
+    pub seed_money: u32,
+    /// Seeds each player drafts, and so places over the match.
+    #[derivative(Default(value = "3"))]
+    pub seeds_per_player: u32,
+    /// Rounds of a [`VictoryCondition::MostTerritory`] match.
+    #[derivative(Default(value = "3"))]
+    pub rounds: u32,
+    pub turn_order: TurnOrder,
+    pub victory: VictoryCondition,
+    /// Share of the board needed for a [`VictoryCondition::Threshold`] win.
+    #[derivative(Default(value = "0.5"))]
+    pub victory_share: f32,
     pub layout: SimLayout,
+    pub boundary: BoundaryMode,
+    pub neighborhood: Neighborhood,
+    /// Default rule for every team. This is the only rule the compute shader runs.
+    pub rule: TransitionTable,
+    /// Drives every random draw: layouts and probabilistic rules.
+    #[derivative(Default(value = "SimSeed::random()"))]
+    pub seed: SimSeed,
     pub use_compute: bool,
 }
 impl SimSettings {
-    pub fn get_player_color(&self, id: PlayerID) -> [u8; 4] {
-        self.teams
-            .iter()
-            .find(|team| team.players.contains(&id))
-            .expect("team")
-            .color
+    pub fn cell_color(&self, cell: &Cell) -> [u8; 4] {
+        match (cell.kind, cell.owner) {
+            (CellKind::Empty, _) => *BLACK,
+            (CellKind::Active, _) => *WHITE,
+            (CellKind::Captured, Some(team)) => self.teams.get(team).map_or(*BLACK, |t| t.color),
+            (CellKind::Captured, None) => *BLACK,
+            (CellKind::Dying, _) => {
+                // Fade from white towards black, one shade per stage.
+                let fade = cell.state as f32 / (self.rule.stages as f32 + 1.);
+                let shade = (255. * (1. - fade)).round() as u8;
+                [shade, shade, shade, 255]
+            }
+            // Halfway between the owner's color and white.
+            (CellKind::Dormant, owner) => {
+                let color = owner
+                    .and_then(|team| self.teams.get(team))
+                    .map_or(*WHITE, |t| t.color);
+                color.map(|c| c / 2 + 128)
+            }
+        }
     }
 }
 
-pub const DISPLAY_FACTOR: u32 = 1;
-pub const IMG_SIZE: u32 = 512;
-// TODO: Replace with SimSize setting
-pub const SIM_SIZE: u32 = IMG_SIZE / DISPLAY_FACTOR;
 pub const WORKGROUP_SIZE: u32 = 8; // workgroup = num threads
 pub const SHADER_ASSET_PATH: &str = "shader/simulation.wgsl";
 
@@ -0,0 +1,222 @@
+//! Logical cell state. [`CellGrid`] is the source of truth for the board;
+//! images are only ever produced from it by the colorize pass.
+
+use bevy::prelude::*;
+
+use crate::sim::{
+    DormantSeed,
+    data::{BoundaryMode, CellCondition, HILL, SimSettings, TeamID},
+};
+
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum CellKind {
+    #[default]
+    Empty,
+    Active,
+    /// Territory held by [`Cell::owner`].
+    Captured,
+    /// Dead but still decaying. [`Cell::state`] holds the stage.
+    Dying,
+    /// A seed waiting for its wake condition. [`Cell::state`] indexes
+    /// [`CellGrid::seeds`].
+    Dormant,
+}
+
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct Cell {
+    pub kind: CellKind,
+    /// The team holding captured territory or a dormant seed. Active and
+    /// dying cells remember the team whose stamp they grew from, if any.
+    pub owner: Option<TeamID>,
+    /// Extra per-cell state for rules which need it.
+    pub state: u8,
+}
+impl Cell {
+    pub const EMPTY: Self = Self {
+        kind: CellKind::Empty,
+        owner: None,
+        state: 0,
+    };
+    pub const ACTIVE: Self = Self {
+        kind: CellKind::Active,
+        owner: None,
+        state: 0,
+    };
+    pub fn dying(stage: u8) -> Self {
+        Self {
+            kind: CellKind::Dying,
+            owner: None,
+            state: stage,
+        }
+    }
+    pub fn dormant(team: TeamID, seed: u8) -> Self {
+        Self {
+            kind: CellKind::Dormant,
+            owner: Some(team),
+            state: seed,
+        }
+    }
+    pub fn with_owner(self, owner: Option<TeamID>) -> Self {
+        Self { owner, ..self }
+    }
+    pub fn captured(team: TeamID) -> Self {
+        Self {
+            kind: CellKind::Captured,
+            owner: Some(team),
+            state: 0,
+        }
+    }
+    /// How this cell looks from `team`'s point of view.
+    pub fn condition(&self, team: TeamID) -> CellCondition {
+        match self.kind {
+            CellKind::Empty => CellCondition::Empty,
+            CellKind::Active => CellCondition::Active,
+            CellKind::Captured if self.owner == Some(team) => CellCondition::Owned,
+            CellKind::Captured => CellCondition::Enemy,
+            CellKind::Dying => CellCondition::Dying(self.state),
+            CellKind::Dormant => CellCondition::Dormant,
+        }
+    }
+    /// Packs the cell into an rgba8 texel for the compute shader.
+    /// `[kind, owner + 1 (0 = none), state, 255]`
+    pub fn encode(&self) -> [u8; 4] {
+        let kind = match self.kind {
+            CellKind::Empty => 0,
+            CellKind::Active => 1,
+            CellKind::Captured => 2,
+            CellKind::Dying => 3,
+            CellKind::Dormant => 4,
+        };
+        let owner = self.owner.map_or(0, |team| team as u8 + 1);
+        [kind, owner, self.state, 255]
+    }
+    pub fn decode(texel: &[u8]) -> Self {
+        let kind = match texel[0] {
+            1 => CellKind::Active,
+            2 => CellKind::Captured,
+            3 => CellKind::Dying,
+            4 => CellKind::Dormant,
+            _ => CellKind::Empty,
+        };
+        let owner = texel[1].checked_sub(1).map(|team| team as TeamID);
+        Self {
+            kind,
+            owner,
+            state: texel[2],
+        }
+    }
+}
+
+/// The board, stored row by row.
+#[derive(Resource, Clone, Debug, Default, PartialEq)]
+pub struct CellGrid {
+    pub size: UVec2,
+    pub cells: Vec<Cell>,
+    /// Every seed planted this game, woken or not.
+    pub seeds: Vec<DormantSeed>,
+}
+impl CellGrid {
+    /// A dormant cell indexes its seed with [`Cell::state`], so a board holds
+    /// at most this many seeds.
+    pub const MAX_SEEDS: usize = u8::MAX as usize + 1;
+
+    pub fn new(size: UVec2) -> Self {
+        Self {
+            size,
+            cells: vec![Cell::EMPTY; (size.x * size.y) as usize],
+            seeds: Vec::new(),
+        }
+    }
+    pub fn index(&self, pos: UVec2) -> usize {
+        (pos.y * self.size.x + pos.x) as usize
+    }
+    pub fn position(&self, index: usize) -> UVec2 {
+        UVec2::new(index as u32 % self.size.x, index as u32 / self.size.x)
+    }
+    pub fn contains(&self, pos: UVec2) -> bool {
+        pos.x < self.size.x && pos.y < self.size.y
+    }
+    pub fn get(&self, pos: UVec2) -> Option<Cell> {
+        self.contains(pos).then(|| self.cells[self.index(pos)])
+    }
+    /// Like [`CellGrid::get`], but positions past the edge are resolved
+    /// according to `boundary`.
+    pub fn get_bounded(&self, pos: IVec2, boundary: BoundaryMode) -> Cell {
+        match boundary.resolve(pos, self.size) {
+            Some(pos) => self.cells[self.index(pos)],
+            None if boundary == BoundaryMode::Wall => Cell::ACTIVE,
+            None => Cell::EMPTY,
+        }
+    }
+    /// Out of range positions are ignored.
+    pub fn set(&mut self, pos: UVec2, cell: Cell) {
+        if self.contains(pos) {
+            let index = self.index(pos);
+            self.cells[index] = cell;
+        }
+    }
+    /// Places a dormant seed. Fails if `pos` is out of range or the grid
+    /// already holds as many seeds as a cell can index.
+    pub fn plant(&mut self, pos: UVec2, seed: DormantSeed) -> Option<usize> {
+        let index = self.seeds.len();
+        if !self.contains(pos) || index >= Self::MAX_SEEDS {
+            return None;
+        }
+        self.set(pos, Cell::dormant(seed.owner, index as u8));
+        self.seeds.push(seed);
+        Some(index)
+    }
+    /// Writes display colors into an rgba8 image of the same size.
+    /// Empty cells in `zones` are marked, see [`crate::sim::marked_zones`].
+    pub fn colorize(&self, image: &mut Image, settings: &SimSettings, zones: &[URect]) {
+        let Some(data) = image.data.as_mut() else {
+            return;
+        };
+        for (i, (cell, texel)) in self.cells.iter().zip(data.chunks_exact_mut(4)).enumerate() {
+            let pos = self.position(i);
+            let color = if cell.kind == CellKind::Empty && zones.iter().any(|z| z.contains(pos)) {
+                *HILL
+            } else {
+                settings.cell_color(cell)
+            };
+            texel.copy_from_slice(&color);
+        }
+    }
+    /// Writes [`Cell::encode`]d texels into an rgba8 image of the same size.
+    pub fn encode(&self, image: &mut Image) {
+        let Some(data) = image.data.as_mut() else {
+            return;
+        };
+        for (cell, texel) in self.cells.iter().zip(data.chunks_exact_mut(4)) {
+            texel.copy_from_slice(&cell.encode());
+        }
+    }
+    /// Reads [`Cell::encode`]d texels back, e.g. from a GPU readback.
+    /// Rows may be padded, so the stride is taken from the data length.
+    /// Seeds are only tracked here, so a readback taken before a seed woke
+    /// can't bring it back.
+    pub fn decode(&mut self, data: &[u8]) {
+        let stride = data.len() / self.size.y.max(1) as usize;
+        for y in 0..self.size.y {
+            let row = &data[y as usize * stride..];
+            for x in 0..self.size.x {
+                let offset = x as usize * 4;
+                let index = self.index(UVec2::new(x, y));
+                let cell = Cell::decode(&row[offset..offset + 4]);
+                let woken = cell.kind == CellKind::Dormant
+                    && self
+                        .seeds
+                        .get(cell.state as usize)
+                        .is_none_or(|seed| seed.awake);
+                if !woken {
+                    self.cells[index] = cell;
+                }
+            }
+        }
+    }
+}
+
+/// The board with the pending stamp applied, shown while hovering.
+/// Becomes the [`CellGrid`] once the stamp is placed.
+#[derive(Resource, Clone, Debug, Default, PartialEq, Deref, DerefMut)]
+pub struct PreviewGrid(pub CellGrid);
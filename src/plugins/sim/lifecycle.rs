@@ -1,4 +1,14 @@
-use crate::{sim::data::*, ui::widgets::sim_image::SimImageNode};
+use std::sync::Arc;
+
+use rand::Rng;
+
+use crate::{
+    sim::{
+        Cell, CellGrid, CellKind, PreviewGrid, SeedActivatedEvent, SimRules, data::*,
+        render::cpu::SoftwareSimSet,
+    },
+    ui::widgets::sim_image::SimImageNode,
+};
 use bevy::{
     asset::RenderAssetUsages,
     prelude::*,
@@ -12,59 +22,68 @@ impl Plugin for SimLifecyclePlugin {
             .init_state::<SimState>()
             .add_systems(
                 OnEnter(SimState::Init),
-                (init_images, spawn_sprite, populate, init_timestep).chain(),
+                (
+                    init_images,
+                    init_grid,
+                    spawn_sprite,
+                    populate,
+                    init_timestep,
+                    init_rules,
+                )
+                    .chain(),
+            )
+            .add_systems(OnEnter(SimState::Running), (unpause, start_turn))
+            .add_systems(
+                OnTransition {
+                    exited: SimState::Running,
+                    entered: SimState::Paused,
+                },
+                commit_state,
             )
-            .add_systems(OnEnter(SimState::Running), unpause)
-            .add_systems(OnEnter(SimState::Paused), (commit_state, pause))
+            .add_systems(OnEnter(SimState::Paused), pause)
             .add_systems(OnEnter(SimState::Closed), cleanup)
-            .add_systems(FixedUpdate, update)
-            .add_observer(on_stamp);
+            .add_systems(
+                FixedUpdate,
+                update
+                    .run_if(in_state(SimState::Running))
+                    .after(SoftwareSimSet),
+            )
+            .add_observer(extend_turn);
     }
 }
 
-fn commit_state(
-    sim_imgs: Res<SimImages>,
-    mut imgs: ResMut<Assets<Image>>,
-    node: Single<&ImageNode, With<SimImageNode>>,
+/// Active cells become territory of the player who just finished their turn.
+/// Dying cells are still the tail end of that turn's chain reaction,
+/// so they are captured as well instead of being left to decay.
+/// After a simultaneous round each cell goes to the team whose stamp it grew
+/// from, see [`Cell::owner`], and is left empty if it grew from nobody's.
+/// Only runs after a simulated turn, not when a freshly populated board pauses.
+pub fn commit_state(
+    mut grid: ResMut<CellGrid>,
     settings: Res<SimSettings>,
     mut gs: ResMut<SimGameplayState>,
 ) {
-    let current_img = imgs.get_mut(&node.image).expect("current_img");
-    let color = settings.get_player_color(gs.current_player);
-    let size = current_img.size();
-    for x in 0..size.x {
-        for y in 0..size.y {
-            let data = current_img
-                .pixel_bytes_mut(UVec3::new(x, y, 0))
-                .expect("oob");
-            if data == WHITE {
-                data[0] = color[0];
-                data[1] = color[1];
-                data[2] = color[2];
-            }
+    let simultaneous = settings.turn_order == TurnOrder::Simultaneous;
+    let team = settings.players[gs.current_player].team;
+    for cell in grid.cells.iter_mut() {
+        if matches!(cell.kind, CellKind::Active | CellKind::Dying) {
+            let owner = if simultaneous { cell.owner } else { Some(team) };
+            *cell = owner.map_or(Cell::EMPTY, Cell::captured);
         }
     }
-    let preview_image = imgs
-        .get_mut(&sim_imgs.preview_texture)
-        .expect("preview texture")
-        .clone();
-    imgs.get_mut(&sim_imgs.texture_a)
-        .expect("tex_a")
-        .clone_from(&preview_image);
-    imgs.get_mut(&sim_imgs.texture_b)
-        .expect("tex_b")
-        .clone_from(&preview_image);
-
-    gs.current_player = (gs.current_player + 1) % settings.players.len();
+    // Simultaneous turns are counted as players confirm, see `main_loop`.
+    if !simultaneous {
+        gs.turn += 1;
+    }
+    gs.current_player = settings
+        .turn_order
+        .player(gs.turn, &settings.players, &settings.teams);
 }
 
-fn update(
-    mut gameplay: ResMut<SimGameplayState>,
-    settings: Res<SimSettings>,
-    mut state: ResMut<NextState<SimState>>,
-) {
+pub fn update(mut gameplay: ResMut<SimGameplayState>, mut state: ResMut<NextState<SimState>>) {
     gameplay.num_steps += 1;
-    if gameplay.num_steps > settings.steps_per_turn {
+    gameplay.generation = gameplay.generation.wrapping_add(1);
+    if gameplay.num_steps > gameplay.step_limit {
         gameplay.num_steps = 0;
         state.set(SimState::Paused);
     }
@@ -75,85 +94,94 @@ fn init_images(
     mut images: ResMut<Assets<Image>>,
     settings: Res<SimSettings>,
 ) {
-    let (asset_usage, format) = if settings.use_compute {
-        // !NB! compute shader should reflect this
-        (RenderAssetUsages::RENDER_WORLD, TextureFormat::Rgba8Unorm)
-    } else {
-        (
-            RenderAssetUsages::RENDER_WORLD | RenderAssetUsages::MAIN_WORLD,
-            TextureFormat::Rgba8UnormSrgb,
-        )
+    let size = Extent3d {
+        width: settings.size,
+        height: settings.size,
+        depth_or_array_layers: 1,
     };
-    let mut image = Image::new_fill(
-        Extent3d {
-            width: settings.size,
-            height: settings.size,
-            depth_or_array_layers: 1,
-        },
+    let asset_usage = RenderAssetUsages::RENDER_WORLD | RenderAssetUsages::MAIN_WORLD;
+    let mut display = Image::new_fill(
+        size,
         TextureDimension::D2,
-        &[0, 0, 0, 0],
-        format,
+        BLACK,
+        TextureFormat::Rgba8UnormSrgb,
         asset_usage,
     );
-    image.texture_descriptor.usage = TextureUsages::COPY_DST | TextureUsages::TEXTURE_BINDING;
-    if settings.use_compute {
-        image.texture_descriptor.usage |= TextureUsages::STORAGE_BINDING;
-    }
+    display.texture_descriptor.usage = TextureUsages::COPY_DST | TextureUsages::TEXTURE_BINDING;
+
+    // !NB! compute shader should reflect this
+    let mut buffer = Image::new_fill(
+        size,
+        TextureDimension::D2,
+        &Cell::EMPTY.encode(),
+        TextureFormat::Rgba8Unorm,
+        asset_usage,
+    );
+    buffer.texture_descriptor.usage = TextureUsages::COPY_DST
+        | TextureUsages::COPY_SRC
+        | TextureUsages::TEXTURE_BINDING
+        | TextureUsages::STORAGE_BINDING;
 
     commands.insert_resource(SimImages {
-        preview_texture: images.add(image.clone()),
-        texture_a: images.add(image.clone()),
-        texture_b: images.add(image),
+        preview_texture: images.add(display.clone()),
+        display_texture: images.add(display),
+        texture_a: images.add(buffer.clone()),
+        texture_b: images.add(buffer.clone()),
+        texture_out: images.add(buffer),
     });
 }
 
+fn init_grid(mut commands: Commands, settings: Res<SimSettings>) {
+    let grid = CellGrid::new(UVec2::splat(settings.size));
+    commands.insert_resource(PreviewGrid(grid.clone()));
+    commands.insert_resource(grid);
+}
+
 fn populate(
-    sprite: Single<&ImageNode, With<SimSprite>>,
-    mut images: ResMut<Assets<Image>>,
+    mut grid: ResMut<CellGrid>,
     mut next: ResMut<NextState<SimState>>,
     settings: Res<SimSettings>,
 ) {
-    let img = images.get_mut(sprite.image.id()).unwrap();
-    let size = img.size();
+    let size = grid.size;
+    let mut rng = settings.seed.rng();
     for x in 0..size.x {
         for y in 0..size.y {
-            let color = match settings.layout {
+            let cell = match settings.layout {
                 SimLayout::Random => {
                     let len = settings.teams.len() + 2;
-                    let res = rand::random_range(0..len);
+                    let res = rng.random_range(0..len);
                     match res {
-                        0 => WHITE,
-                        1 => BLACK,
-                        _ => &settings.teams[res - 2].color,
+                        0 => Cell::ACTIVE,
+                        1 => Cell::EMPTY,
+                        _ => Cell::captured(res - 2),
                     }
                 }
                 // assumes 2 teams...
                 // 4 teams would have quadrants, etc
                 SimLayout::Horiz5050 => {
                     if y < size.y / 2 {
-                        &settings.teams[0].color
+                        Cell::captured(0)
                     } else {
-                        &settings.teams[1].color
+                        Cell::captured(1)
                     }
                 }
                 SimLayout::Vert5050 => {
                     if x < size.x / 2 {
-                        &settings.teams[0].color
+                        Cell::captured(0)
                     } else {
-                        &settings.teams[1].color
+                        Cell::captured(1)
                     }
                 }
                 SimLayout::Rand5050 => {
-                    if rand::random_bool(0.5) {
-                        &settings.teams[0].color
+                    if rng.random_bool(0.5) {
+                        Cell::captured(0)
                     } else {
-                        &settings.teams[1].color
+                        Cell::captured(1)
                     }
                 }
-                SimLayout::Empty => BLACK,
+                SimLayout::Empty => Cell::EMPTY,
             };
-            img.set_color_at(x, y, Color::srgb_u8(color[0], color[1], color[2]))
-                .unwrap();
+            grid.set(UVec2::new(x, y), cell);
         }
     }
     next.set(SimState::Paused);
@@ -163,58 +191,65 @@ fn init_timestep(mut time: ResMut<Time<Fixed>>, settings: Res<SimSettings>) {
     time.set_timestep_hz(settings.timestep as f64);
 }
 
-fn unpause(
+fn init_rules(
+    mut rules: ResMut<SimRules>,
+    mut gameplay: ResMut<SimGameplayState>,
+    settings: Res<SimSettings>,
+) {
+    rules.default = Arc::new(settings.rule);
+    // Random draws depend on the generation, so restart it for reproducible runs.
+    gameplay.num_steps = 0;
+    gameplay.generation = 0;
+    // A fresh board is a fresh game.
+    gameplay.turn = 0;
+    gameplay.current_player = settings
+        .turn_order
+        .player(0, &settings.players, &settings.teams);
+    gameplay.preview_locked = false;
+    info!("Initializing sim with seed {}", settings.seed);
+}
+
+fn start_turn(mut gameplay: ResMut<SimGameplayState>, settings: Res<SimSettings>) {
+    gameplay.num_steps = 0;
+    gameplay.step_limit = settings.steps_per_turn;
+}
+
+/// Chain reactions: every seed that wakes restarts the countdown,
+/// up to `max_steps_per_turn` steps in total.
+fn extend_turn(
+    _trigger: Trigger<SeedActivatedEvent>,
+    settings: Res<SimSettings>,
+    mut gameplay: ResMut<SimGameplayState>,
+) {
+    let cap = settings.max_steps_per_turn.max(settings.steps_per_turn);
+    gameplay.step_limit = (gameplay.num_steps + settings.steps_per_turn).min(cap);
+}
+
+pub fn unpause(
     mut time: ResMut<Time<Virtual>>,
     mut image_node: Single<&mut ImageNode, With<SimImageNode>>,
     sim_images: Res<SimImages>,
-    mut images: ResMut<Assets<Image>>,
 ) {
+    // Placements are committed to the grid before the sim runs,
+    // so the preview is only for show.
     time.unpause();
-    image_node.image = sim_images.texture_a.clone();
-    let preview_image = images
-        .get_mut(&sim_images.preview_texture)
-        .expect("preview texture")
-        .clone();
-    images
-        .get_mut(&sim_images.texture_a)
-        .expect("tex_a")
-        .clone_from(&preview_image);
-    images
-        .get_mut(&sim_images.texture_b)
-        .expect("tex_b")
-        .clone_from(&preview_image);
+    image_node.image = sim_images.display_texture.clone();
 }
 
 fn pause(
     mut time: ResMut<Time<Virtual>>,
-    sim_imgs: ResMut<SimImages>,
+    sim_imgs: Res<SimImages>,
     settings: Res<SimSettings>,
     mut image_nodes: Query<&mut ImageNode>,
-    mut images: ResMut<Assets<Image>>,
+    grid: Res<CellGrid>,
+    mut preview: ResMut<PreviewGrid>,
 ) {
     time.pause();
-    let parent_node = settings
+    preview.0.clone_from(&grid);
+    if let Some(mut node) = settings
         .parent_node
-        .and_then(|p| image_nodes.get_mut(p).ok());
-    let current_handle = parent_node
-        .as_ref()
-        .map_or(&sim_imgs.preview_texture, |n| &n.image)
-        .clone();
-    let current_img = images.get(&current_handle).expect("current_img").clone();
-    images
-        .get_mut(&sim_imgs.texture_a)
-        .expect("tex_a")
-        .clone_from(&current_img);
-    images
-        .get_mut(&sim_imgs.texture_b)
-        .expect("tex_b")
-        .clone_from(&current_img);
-    images
-        .get_mut(&sim_imgs.preview_texture)
-        .expect("tex_p")
-        .clone_from(&current_img);
-
-    if let Some(mut node) = parent_node {
+        .and_then(|p| image_nodes.get_mut(p).ok())
+    {
         node.image = sim_imgs.preview_texture.clone();
     }
 }
@@ -229,7 +264,7 @@ pub fn spawn_sprite(
         let mut parent = image_nodes
             .get_mut(parent_node)
             .expect("Could not get parent node!");
-        parent.image = images.texture_a.clone();
+        parent.image = images.display_texture.clone();
         commands
             .get_entity(parent_node)
             .expect("get_entity")
@@ -239,7 +274,3 @@ pub fn spawn_sprite(
 pub fn cleanup(mut commands: Commands, query: Single<Entity, With<SimSprite>>) {
     commands.get_entity(query.entity()).unwrap().despawn();
 }
-
-fn on_stamp(_trigger: Trigger<StampEvent>, mut state: ResMut<NextState<SimState>>) {
-    state.set(SimState::Running);
-}
@@ -1,12 +1,35 @@
 use bevy::prelude::*;
 
 pub use data::*;
+pub use grid::*;
+pub use lifecycle::commit_state;
+pub use neighborhood::*;
+pub use random::*;
+pub use render::cpu::step_cells;
+pub use rules::*;
+pub use scoring::*;
+pub use seeds::*;
+pub use stats::*;
+pub use turn_order::*;
+pub use victory::*;
 
-use crate::sim::{lifecycle::SimLifecyclePlugin, render::cpu::CpuSimPlugin};
+use crate::sim::{
+    lifecycle::SimLifecyclePlugin,
+    render::{colorize::ColorizePlugin, cpu::CpuSimPlugin},
+};
 
 mod data;
+mod grid;
 mod lifecycle;
+mod neighborhood;
+mod random;
 mod render;
+mod rules;
+mod scoring;
+mod seeds;
+mod stats;
+mod turn_order;
+mod victory;
 
 pub struct SimPlugin;
 impl Plugin for SimPlugin {
@@ -16,80 +39,64 @@ impl Plugin for SimPlugin {
             {
                 app.add_plugins(crate::sim::render::gpu::GpuSimPlugin);
             }
-            app.add_plugins((CpuSimPlugin, SimLifecyclePlugin))
-                .insert_resource(SimSettings {
-                    teams: vec![
-                        Team {
-                            id: 0,
-                            name: "A".into(),
-                            players: vec![0],
-                            color: [255, 0, 0, 255],
-                        },
-                        Team {
-                            id: 1,
-                            name: "B".into(),
-                            players: vec![1],
-                            color: [0, 0, 255, 255],
-                        },
-                    ],
-                    players: vec![
-                        Player {
-                            name: "Player 1".into(),
-                            team: 0,
-                        },
-                        Player {
-                            name: "Player 2".into(),
-                            team: 1,
-                        },
-                    ],
-                    ..Default::default()
-                })
-                .init_resource::<SimImages>()
-                .init_resource::<SimGameplayState>()
-                // todo: crashing
-                // this is super annoying!
-                .configure_sets(
-                    Update,
-                    (
-                        GpuSimSystems.run_if(run_gpu_systems),
-                        CpuSimSystems.run_if(run_cpu_systems),
-                    ),
-                )
-                .configure_sets(
-                    FixedUpdate,
-                    (
-                        GpuSimSystems.run_if(run_gpu_systems),
-                        CpuSimSystems.run_if(run_cpu_systems),
-                    ),
-                )
-                .configure_sets(
-                    OnEnter(SimState::Init),
-                    (
-                        GpuSimSystems.run_if(run_gpu_systems),
-                        CpuSimSystems.run_if(run_cpu_systems),
-                    ),
-                )
-                .configure_sets(
-                    OnEnter(SimState::Running),
-                    (
-                        GpuSimSystems.run_if(run_gpu_systems),
-                        CpuSimSystems.run_if(run_cpu_systems),
-                    ),
-                )
-                .configure_sets(
-                    OnEnter(SimState::Paused),
-                    (
-                        GpuSimSystems.run_if(run_gpu_systems),
-                        CpuSimSystems.run_if(run_cpu_systems),
-                    ),
-                )
-                .configure_sets(
-                    OnEnter(SimState::Closed),
-                    (
-                        GpuSimSystems.run_if(run_gpu_systems),
-                        CpuSimSystems.run_if(run_cpu_systems),
-                    ),
-                )
+            app.add_plugins((
+                CpuSimPlugin,
+                ColorizePlugin,
+                SimLifecyclePlugin,
+                SeedPlugin,
+                TerritoryStatsPlugin,
+                VictoryPlugin,
+            ))
+            .init_resource::<SimSettings>()
+            .init_resource::<SimImages>()
+            .init_resource::<SimGameplayState>()
+            .init_resource::<SimRules>()
+            .init_resource::<CellGrid>()
+            .init_resource::<PreviewGrid>()
+            // todo: crashing
+            // this is super annoying!
+            .configure_sets(
+                Update,
+                (
+                    GpuSimSystems.run_if(run_gpu_systems),
+                    CpuSimSystems.run_if(run_cpu_systems),
+                ),
+            )
+            .configure_sets(
+                FixedUpdate,
+                (
+                    GpuSimSystems.run_if(run_gpu_systems),
+                    CpuSimSystems.run_if(run_cpu_systems),
+                ),
+            )
+            .configure_sets(
+                OnEnter(SimState::Init),
+                (
+                    GpuSimSystems.run_if(run_gpu_systems),
+                    CpuSimSystems.run_if(run_cpu_systems),
+                ),
+            )
+            .configure_sets(
+                OnEnter(SimState::Running),
+                (
+                    GpuSimSystems.run_if(run_gpu_systems),
+                    CpuSimSystems.run_if(run_cpu_systems),
+                ),
+            )
+            .configure_sets(
+                OnEnter(SimState::Paused),
+                (
+                    GpuSimSystems.run_if(run_gpu_systems),
+                    CpuSimSystems.run_if(run_cpu_systems),
+                ),
+            )
+            .configure_sets(
+                OnEnter(SimState::Closed),
+                (
+                    GpuSimSystems.run_if(run_gpu_systems),
+                    CpuSimSystems.run_if(run_cpu_systems),
+                ),
+            )
         };
     }
 }
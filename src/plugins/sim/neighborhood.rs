@@ -0,0 +1,87 @@
+//! Which cells count as neighbors. The compute shader mirrors
+//! [`Neighborhood::contains`] in `in_neighborhood`.
+
+use std::fmt::Display;
+
+use bevy::prelude::*;
+
+/// Largest supported Moore radius. Bounds the size of the transition table.
+pub const MAX_RADIUS: u32 = 3;
+/// Neighbor count of a Moore neighborhood with [`MAX_RADIUS`].
+pub const MAX_NEIGHBORS: usize = ((2 * MAX_RADIUS + 1) * (2 * MAX_RADIUS + 1) - 1) as usize;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum Neighborhood {
+    /// Every cell within `radius` on both axes. Radius 1 is the classic
+    /// 8 cell neighborhood, larger radii give Larger than Life rules.
+    Moore { radius: u32 },
+    /// The 4 orthogonally adjacent cells.
+    VonNeumann,
+    /// The 6 neighbors of a hex grid drawn as a skewed square grid,
+    /// i.e. the Moore neighborhood without the top right and bottom left corners.
+    Hex,
+}
+impl Default for Neighborhood {
+    fn default() -> Self {
+        Self::Moore { radius: 1 }
+    }
+}
+impl Neighborhood {
+    /// The discriminant the compute shader receives.
+    pub fn kind(&self) -> u32 {
+        match self {
+            Self::Moore { .. } => 0,
+            Self::VonNeumann => 1,
+            Self::Hex => 2,
+        }
+    }
+    /// Half the width of the square enclosing the neighborhood.
+    pub fn radius(&self) -> u32 {
+        match self {
+            Self::Moore { radius } => (*radius).clamp(1, MAX_RADIUS),
+            Self::VonNeumann | Self::Hex => 1,
+        }
+    }
+    pub fn contains(&self, offset: IVec2) -> bool {
+        let r = self.radius() as i32;
+        if offset == IVec2::ZERO || offset.abs().max_element() > r {
+            return false;
+        }
+        match self {
+            Self::Moore { .. } => true,
+            Self::VonNeumann => offset.x.abs() + offset.y.abs() <= r,
+            Self::Hex => offset.x * offset.y != -1,
+        }
+    }
+    /// Offsets of every neighbor, row by row.
+    pub fn offsets(&self) -> Vec<IVec2> {
+        let r = self.radius() as i32;
+        (-r..=r)
+            .flat_map(|y| (-r..=r).map(move |x| IVec2::new(x, y)))
+            .filter(|offset| self.contains(*offset))
+            .collect()
+    }
+}
+impl Display for Neighborhood {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Moore { radius: 1 } => write!(f, "Moore"),
+            Self::Moore { radius } => write!(f, "Moore (r={radius})"),
+            Self::VonNeumann => write!(f, "Von Neumann"),
+            Self::Hex => write!(f, "Hexagonal"),
+        }
+    }
+}
+impl TryFrom<&String> for Neighborhood {
+    type Error = anyhow::Error;
+    fn try_from(value: &String) -> anyhow::Result<Self> {
+        match value.as_str() {
+            "Moore" => Ok(Self::Moore { radius: 1 }),
+            "Moore (r=2)" => Ok(Self::Moore { radius: 2 }),
+            "Moore (r=3)" => Ok(Self::Moore { radius: 3 }),
+            "Von Neumann" => Ok(Self::VonNeumann),
+            "Hexagonal" => Ok(Self::Hex),
+            _ => Err(anyhow::anyhow!("No such neighborhood")),
+        }
+    }
+}
@@ -0,0 +1,50 @@
+//! Seeded randomness. Every random draw in the simulation goes through
+//! [`SimSeed`], so a seed and the same placements always give the same board.
+//! The per-cell rolls mirror `hash` and `randomFloat` in `shader/simulation.wgsl`.
+
+use std::fmt::Display;
+
+use rand::SeedableRng;
+use rand_chacha::ChaCha8Rng;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct SimSeed(pub u32);
+impl SimSeed {
+    pub fn random() -> Self {
+        Self(rand::random())
+    }
+    /// Rng for one-off draws such as layouts. Unlike `StdRng` its output is
+    /// stable across rand versions and platforms, so shared seeds keep working.
+    pub fn rng(&self) -> ChaCha8Rng {
+        ChaCha8Rng::seed_from_u64(self.0 as u64)
+    }
+    /// Uniform roll in `[0, 1)` for one cell on one step.
+    pub fn roll(&self, generation: u32, x: u32, y: u32) -> f32 {
+        random_float(cell_id(x, y) ^ hash(generation ^ hash(self.0)))
+    }
+}
+impl Display for SimSeed {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+pub fn cell_id(x: u32, y: u32) -> u32 {
+    (y << 16) | x
+}
+
+pub fn hash(value: u32) -> u32 {
+    let mut state = value;
+    state ^= 2747636419;
+    state = state.wrapping_mul(2654435769);
+    state ^= state >> 16;
+    state = state.wrapping_mul(2654435769);
+    state ^= state >> 16;
+    state = state.wrapping_mul(2654435769);
+    state
+}
+
+/// Uses the top 24 bits so the result is exact in an f32 and never reaches 1.
+pub fn random_float(value: u32) -> f32 {
+    (hash(value) >> 8) as f32 / (1 << 24) as f32
+}
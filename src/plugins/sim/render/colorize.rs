@@ -0,0 +1,45 @@
+//! Produces the display images from the cell grids.
+//! Runs for both the CPU and GPU backends.
+
+use bevy::prelude::*;
+
+use crate::sim::{CellGrid, PreviewGrid, SimGameplayState, SimImages, SimSettings, marked_zones};
+
+pub struct ColorizePlugin;
+impl Plugin for ColorizePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            PostUpdate,
+            (
+                colorize_grid.run_if(resource_exists_and_changed::<CellGrid>),
+                colorize_preview.run_if(resource_exists_and_changed::<PreviewGrid>),
+            ),
+        );
+    }
+}
+
+fn colorize_grid(
+    grid: Res<CellGrid>,
+    sim_images: Res<SimImages>,
+    settings: Res<SimSettings>,
+    gameplay: Res<SimGameplayState>,
+    mut images: ResMut<Assets<Image>>,
+) {
+    if let Some(image) = images.get_mut(&sim_images.display_texture) {
+        let zones = marked_zones(&settings, &gameplay, grid.size);
+        grid.colorize(image, &settings, &zones);
+    }
+}
+
+fn colorize_preview(
+    preview: Res<PreviewGrid>,
+    sim_images: Res<SimImages>,
+    settings: Res<SimSettings>,
+    gameplay: Res<SimGameplayState>,
+    mut images: ResMut<Assets<Image>>,
+) {
+    if let Some(image) = images.get_mut(&sim_images.preview_texture) {
+        let zones = marked_zones(&settings, &gameplay, preview.size);
+        preview.colorize(image, &settings, &zones);
+    }
+}
@@ -1,12 +1,12 @@
 //! The web module uses CPU based rendering.
 
-use std::sync::{Arc, Mutex, RwLock};
+use std::sync::Arc;
 
 use bevy::{prelude::*, tasks::ComputeTaskPool};
 
 use crate::sim::{
-    BLACK, PixelColor, SimImages, SimSprite, SimState, WHITE,
-    data::{CellCondition, CellResult},
+    Cell, CellGrid, CellKind, CellRule, SimGameplayState, SimRules, SimSettings, SimState,
+    data::{CellResult, CpuSimSystems, TeamID},
 };
 
 #[derive(SystemSet, Debug, Copy, Clone, PartialEq, Eq, Hash)]
@@ -20,132 +20,105 @@ impl Plugin for CpuSimPlugin {
                 FixedUpdate,
                 (draw)
                     .run_if(in_state(SimState::Running))
-                    .in_set(SoftwareSimSet),
+                    .in_set(SoftwareSimSet)
+                    .in_set(CpuSimSystems),
             )
         };
     }
 }
 
 fn draw(
-    mut sprite: Single<&mut ImageNode, With<SimSprite>>,
-    image_handles: Res<SimImages>,
-    mut images: ResMut<Assets<Image>>,
+    mut grid: ResMut<CellGrid>,
+    settings: Res<SimSettings>,
+    gameplay: Res<SimGameplayState>,
+    rules: Res<SimRules>,
 ) {
-    // This calculation function should be passed in.
-    // It should be the team's chosen rule.
-    // Some restrictions: The neighborhood _must_ have at least one active cell.
-    let calculate = |current: CellCondition, neighborhood: [CellCondition; 8]| -> CellResult {
-        let num_active = neighborhood
-            .iter()
-            .filter(|c| **c == CellCondition::Active)
-            .count();
-        let spawn = current == CellCondition::Empty && num_active == 3;
-        let stay_alive = num_active == 2 || num_active == 3;
-        let die = current == CellCondition::Active && !(2..=3).contains(&num_active);
-
-        if spawn || stay_alive {
-            CellResult::Active
-        } else if die {
-            CellResult::Empty
-        } else {
-            CellResult::Untouched
-        }
-    };
-    // this should also be passed in for the current team
-    // stored as a resource or an entity
-    let team_color: PixelColor = &[255, 0, 0, 255];
+    // Cells store their owning team, so owned and enemy territory is
+    // relative to whoever is playing this turn.
+    let team = gameplay.sim_team(&settings);
+    grid.cells = step_cells(&grid, team, rules.get(team), &settings, gameplay.generation);
+}
 
-    let next_handle = if sprite.image == image_handles.texture_a {
-        image_handles.texture_b.clone()
-    } else {
-        image_handles.texture_a.clone()
-    };
-    let next_img = images.remove(next_handle.id()).expect("next_img");
-    let write_lock = Arc::new(Mutex::new(next_img));
+/// Runs one step of `rule` over `current` from `team`'s point of view.
+/// Also drives the seed previews, which run on boards of their own.
+pub fn step_cells(
+    current: &CellGrid,
+    team: TeamID,
+    rule: Arc<dyn CellRule>,
+    settings: &SimSettings,
+    generation: u32,
+) -> Vec<Cell> {
+    // Some restrictions: The neighborhood _must_ have at least one active cell.
+    let (seed, boundary) = (settings.seed, settings.boundary);
+    let offsets = settings.neighborhood.offsets();
 
-    let current_img = images.get(sprite.image.id()).expect("current_img");
-    let size = current_img.size();
-    let area = size.x * size.y;
-    let read_lock = Arc::new(RwLock::new(current_img));
+    let size = current.size;
+    let area = (size.x * size.y) as usize;
+    let mut next = vec![Cell::EMPTY; area];
 
     // NOTE: This is spawning and closing threads every frame.
     // Would be better to use a consistent threadpool and pass messages.
     let pool = ComputeTaskPool::get();
-    let num_chunks = pool.thread_num() as u32;
-    let chunk_size = area / num_chunks;
+    let chunk_size = area.div_ceil(pool.thread_num().max(1)).max(1);
     pool.scope(|scope| {
-        for chunk_idx in 0..num_chunks {
-            let read_lock = read_lock.clone();
-            let write_lock = write_lock.clone();
+        for (chunk_idx, chunk) in next.chunks_mut(chunk_size).enumerate() {
+            let rule = rule.clone();
+            let offsets = &offsets;
             scope.spawn(async move {
-                let read_lock = read_lock.read().unwrap();
-                let mut iterator = (0..area).map(|i| {
-                    let bytes = read_lock
-                        .pixel_bytes(UVec3::new(i % size.x, i / size.y, 0))
-                        .expect("pixel_bytes")
-                        .to_owned();
-                    let bytes = bytes
-                        .leak()
-                        .as_array::<4>()
-                        .expect("cast pixel_bytes to array[4]");
-                    (i, bytes)
-                });
-                iterator
-                    .advance_by(( chunk_idx * chunk_size ) as usize)
-                    .expect("advance_by");
-                let chunk = (0..chunk_size).map(|_| iterator.next().expect("outside of range"));
-                let colors = chunk.map(|(i, cell)| {
-                    let i = i as i32;
-                    let w = size.x as i32;
-                    #[rustfmt::skip]
-                    let neighborhood = [
-                        i - 1 - w,  i - w,  i + 1 - w,
-                        i - 1,              i + 1,
-                        i - 1 + w,  i + w,  i + 1 + w
-                    ]
-                    .map(|i| {
-                        if i < 0 {
-                            BLACK
-                        } else {
-                            let bytes = read_lock.pixel_bytes(UVec3::new(i as u32 % size.x, i as u32 / size.y, 0))
-                                .unwrap_or(BLACK);
-                            bytes.as_array::<4>().expect("cast to array")
-                        }
-                    })
-                    .map(|cell| get_condition(cell, team_color));
-                    let res = calculate(get_condition(cell, team_color), neighborhood);
-                    let color = match res {
-                        CellResult::Empty => BLACK,
-                        CellResult::Active => WHITE,
+                let mut neighborhood = Vec::with_capacity(offsets.len());
+                let mut neighbors = Vec::with_capacity(offsets.len());
+                for (offset, out) in chunk.iter_mut().enumerate() {
+                    let i = chunk_idx * chunk_size + offset;
+                    let pos = current.position(i);
+                    let cell = current.cells[i];
+                    // Dormant seeds only change by waking, see `seeds::wake_seeds`.
+                    if cell.kind == CellKind::Dormant {
+                        *out = cell;
+                        continue;
+                    }
+                    neighbors.clear();
+                    neighbors.extend(
+                        offsets
+                            .iter()
+                            .map(|delta| current.get_bounded(pos.as_ivec2() + *delta, boundary)),
+                    );
+                    neighborhood.clear();
+                    neighborhood.extend(neighbors.iter().map(|cell| cell.condition(team)));
+                    let roll = seed.roll(generation, pos.x, pos.y);
+                    let res = rule.calculate(cell.condition(team), &neighborhood, roll);
+                    *out = match res {
+                        CellResult::Empty => Cell::EMPTY,
+                        CellResult::Active if cell.kind == CellKind::Active => cell,
+                        CellResult::Active => Cell::ACTIVE.with_owner(birth_owner(&neighbors)),
                         CellResult::Untouched => cell,
+                        CellResult::Dying(stage) => Cell::dying(stage).with_owner(cell.owner),
                     };
-                    (i, color)
-                });
-                let mut write_lock = write_lock.lock().unwrap();
-                let data = write_lock.data.as_mut().expect("data");
-                colors.into_iter().for_each(|(i, color)| {
-                    let offset = i as usize * 4;
-                    data[offset] = color[0];
-                    data[offset + 1] = color[1];
-                    data[offset + 2] = color[2];
-                    data[offset + 3] = color[3];
-                });
+                }
             });
         }
     });
-    let val = Arc::try_unwrap(write_lock).unwrap().into_inner().unwrap();
-    images.insert(next_handle.id(), val);
-    sprite.image = next_handle;
+    next
 }
 
-fn get_condition(pixel: PixelColor, team_color: PixelColor) -> CellCondition {
-    if pixel == team_color {
-        CellCondition::Owned
-    } else if pixel == BLACK {
-        CellCondition::Empty
-    } else if pixel == WHITE {
-        CellCondition::Active
-    } else {
-        CellCondition::Enemy
+/// Newborn cells belong to the team owning most of their active neighbors,
+/// the lowest team on a tie. Mirrored by `birth_owner` in the shader.
+fn birth_owner(neighbors: &[Cell]) -> Option<TeamID> {
+    let mut counts = [0u32; MAX_OWNERS];
+    for cell in neighbors
+        .iter()
+        .filter(|cell| cell.kind == CellKind::Active)
+    {
+        if let Some(count) = cell.owner.and_then(|team| counts.get_mut(team)) {
+            *count += 1;
+        }
     }
+    let (team, count) = counts
+        .iter()
+        .enumerate()
+        .rev()
+        .max_by_key(|(_, count)| **count)?;
+    (*count > 0).then_some(team)
 }
+
+/// Teams whose active cells are told apart, as in the shader.
+const MAX_OWNERS: usize = 4;
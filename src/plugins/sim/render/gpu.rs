@@ -1,11 +1,16 @@
 //! The Native simulation uses compute shaders.
+//! Steps are counted in `FixedUpdate` like on the CPU, see [`GpuSteps`], and
+//! the render graph runs however many were taken since the last frame.
 
-use crate::sim::{data::*, run_gpu_systems};
+use crate::sim::{CellGrid, SeedActivatedEvent, data::*, lifecycle::unpause, run_gpu_systems};
 use bevy::{
     prelude::*,
     render::{
-        Render, RenderApp, RenderSet, extract_resource::ExtractResourcePlugin,
-        graph::CameraDriverLabel, render_graph::RenderGraph,
+        Render, RenderApp, RenderSet,
+        extract_resource::{ExtractResource, ExtractResourcePlugin},
+        gpu_readback::{Readback, ReadbackComplete},
+        graph::CameraDriverLabel,
+        render_graph::RenderGraph,
     },
 };
 
@@ -20,7 +25,23 @@ impl Plugin for GpuSimPlugin {
     fn build(&self, app: &mut App) {
         app.add_plugins((ExtractResourcePlugin::<SimImages>::default(),))
             .add_plugins((ExtractResourcePlugin::<SimSettings>::default(),))
-            .add_systems(FixedUpdate, swap_buffer.in_set(GpuSimSystems));
+            .add_plugins((ExtractResourcePlugin::<SimGameplayState>::default(),))
+            .add_plugins((ExtractResourcePlugin::<GpuSteps>::default(),))
+            .init_resource::<GpuSteps>()
+            .add_systems(Last, queue_steps.run_if(run_gpu_systems))
+            .add_systems(
+                OnEnter(SimState::Running),
+                upload.after(unpause).in_set(GpuSimSystems),
+            )
+            .add_systems(
+                OnEnter(SimState::Paused),
+                stop_readback.in_set(GpuSimSystems),
+            )
+            .add_systems(
+                OnEnter(SimState::Closed),
+                stop_readback.in_set(GpuSimSystems),
+            )
+            .add_observer(reupload);
         let render_app = app.sub_app_mut(RenderApp);
         render_app
             .add_systems(
@@ -50,10 +71,84 @@ fn cleanup(mut render_graph: ResMut<RenderGraph>) {
         .unwrap();
 }
 
-fn swap_buffer(mut sprite: Single<&mut ImageNode, With<SimSprite>>, imgs: Res<SimImages>) {
-    if sprite.image == imgs.texture_a {
-        sprite.image = imgs.texture_b.clone();
-    } else {
-        sprite.image = imgs.texture_a.clone();
+/// Generations to step this frame: step `g` runs with generation `g`, reads
+/// buffer `g % 2` and writes the other, as [`crate::sim::step_cells`] would.
+#[derive(Resource, Clone, Copy, Debug, Default, ExtractResource)]
+pub struct GpuSteps {
+    pub from: u32,
+    pub to: u32,
+}
+impl GpuSteps {
+    pub fn generations(&self) -> impl Iterator<Item = u32> {
+        let (from, to) = (self.from, self.to);
+        (0..to.wrapping_sub(from)).map(move |i| from.wrapping_add(i))
+    }
+}
+
+/// Hands the steps taken in `FixedUpdate` this frame to the render world.
+fn queue_steps(mut steps: ResMut<GpuSteps>, gameplay: Res<SimGameplayState>) {
+    steps.from = steps.to;
+    steps.to = gameplay.generation;
+}
+
+/// Marks the entity reading the simulation back into the [`CellGrid`].
+#[derive(Component)]
+struct SimReadback;
+
+/// Encodes the grid into every buffer, so stepping can start from either.
+fn encode_buffers(grid: &CellGrid, sim_images: &SimImages, images: &mut Assets<Image>) {
+    let handles = [
+        &sim_images.texture_a,
+        &sim_images.texture_b,
+        &sim_images.texture_out,
+    ];
+    for handle in handles {
+        if let Some(image) = images.get_mut(handle) {
+            grid.encode(image);
+        }
+    }
+}
+
+/// Encodes the grid into the buffers and starts reading results back.
+fn upload(
+    mut commands: Commands,
+    grid: Res<CellGrid>,
+    gameplay: Res<SimGameplayState>,
+    sim_images: Res<SimImages>,
+    mut steps: ResMut<GpuSteps>,
+    mut images: ResMut<Assets<Image>>,
+) {
+    encode_buffers(&grid, &sim_images, &mut images);
+    steps.to = gameplay.generation;
+    commands
+        .spawn((
+            Name::new("SimReadback"),
+            SimReadback,
+            Readback::texture(sim_images.texture_out.clone()),
+        ))
+        .observe(
+            |trigger: Trigger<ReadbackComplete>, mut grid: ResMut<CellGrid>| {
+                grid.decode(trigger.event());
+            },
+        );
+}
+
+/// Seeds wake on the CPU, so the expanded grid has to be sent back.
+fn reupload(
+    _trigger: Trigger<SeedActivatedEvent>,
+    grid: Res<CellGrid>,
+    settings: Res<SimSettings>,
+    sim_images: Res<SimImages>,
+    mut images: ResMut<Assets<Image>>,
+) {
+    if !settings.use_compute {
+        return;
+    }
+    encode_buffers(&grid, &sim_images, &mut images);
+}
+
+fn stop_readback(mut commands: Commands, readbacks: Query<Entity, With<SimReadback>>) {
+    for entity in readbacks {
+        commands.entity(entity).despawn();
     }
 }
@@ -10,11 +10,13 @@ use crate::sim::{
 use bevy::{
     prelude::*,
     render::{
+        render_asset::RenderAssets,
         render_graph::{self, RenderLabel},
         render_resource::{
             CachedPipelineState, ComputePassDescriptor, PipelineCache, PipelineCacheError,
         },
         renderer::RenderContext,
+        texture::GpuImage,
     },
 };
 
@@ -25,8 +27,7 @@ pub struct SimLabel;
 pub enum SimNodeState {
     #[default]
     Loading,
-    Init,
-    Update(usize),
+    Ready,
 }
 #[derive(Default, Debug)]
 pub struct SimulationNode {
@@ -39,11 +40,12 @@ impl render_graph::Node for SimulationNode {
         let pipeline_cache = world.resource::<PipelineCache>();
 
         match self.state {
-            // wait for shader to load
+            // wait for shader to load.
+            // the buffers are filled from the CellGrid, so start stepping right away
             SimNodeState::Loading => {
-                match pipeline_cache.get_compute_pipeline_state(pipeline.init_pipeline) {
+                match pipeline_cache.get_compute_pipeline_state(pipeline.update_pipeline) {
                     CachedPipelineState::Ok(_) => {
-                        self.state = SimNodeState::Init;
+                        self.state = SimNodeState::Ready;
                     }
                     CachedPipelineState::Err(PipelineCacheError::ShaderNotLoaded(_)) => {}
                     CachedPipelineState::Err(err) => {
@@ -52,18 +54,7 @@ impl render_graph::Node for SimulationNode {
                     _ => {}
                 }
             }
-            // once initialized, start rendering
-            SimNodeState::Init => {
-                if let CachedPipelineState::Ok(_) =
-                    pipeline_cache.get_compute_pipeline_state(pipeline.update_pipeline)
-                {
-                    self.state = SimNodeState::Update(1);
-                }
-            }
-            // switch buffer
-            SimNodeState::Update(0) => self.state = SimNodeState::Update(1),
-            SimNodeState::Update(1) => self.state = SimNodeState::Update(0),
-            SimNodeState::Update(_) => unreachable!(),
+            SimNodeState::Ready => {}
         }
     }
     fn run(
@@ -72,33 +63,44 @@ impl render_graph::Node for SimulationNode {
         render_context: &mut RenderContext,
         world: &World,
     ) -> Result<(), render_graph::NodeRunError> {
-        let bind_groups = &world.resource::<SimBindGroups>().0;
+        let bind_groups = world.resource::<SimBindGroups>();
+        if matches!(self.state, SimNodeState::Loading) || bind_groups.steps.is_empty() {
+            return Ok(());
+        }
         let pipeline_cache = &world.resource::<PipelineCache>();
         let pipeline = &world.resource::<SimPipeline>();
-        let mut pass = render_context
-            .command_encoder()
-            .begin_compute_pass(&ComputePassDescriptor::default());
-
-        match self.state {
-            SimNodeState::Loading => {}
-            SimNodeState::Init => {
-                let init_pipeline = pipeline_cache
-                    .get_compute_pipeline(pipeline.init_pipeline)
-                    .unwrap();
-                pass.set_bind_group(0, &bind_groups[0], &[]);
-                pass.set_pipeline(init_pipeline);
-                pass.dispatch_workgroups(SIM_SIZE / WORKGROUP_SIZE, SIM_SIZE / WORKGROUP_SIZE, 1);
-            }
-            // switch buffer
-            SimNodeState::Update(idx) => {
-                let update_pipeline = pipeline_cache
-                    .get_compute_pipeline(pipeline.update_pipeline)
-                    .unwrap();
-                pass.set_bind_group(0, &bind_groups[idx], &[]);
-                pass.set_pipeline(update_pipeline);
-                pass.dispatch_workgroups(SIM_SIZE / WORKGROUP_SIZE, SIM_SIZE / WORKGROUP_SIZE, 1);
+        let update_pipeline = pipeline_cache
+            .get_compute_pipeline(pipeline.update_pipeline)
+            .unwrap();
+        let workgroups = world
+            .resource::<SimSettings>()
+            .size
+            .div_ceil(WORKGROUP_SIZE);
+        {
+            let mut pass = render_context
+                .command_encoder()
+                .begin_compute_pass(&ComputePassDescriptor::default());
+            pass.set_pipeline(update_pipeline);
+            // Each step reads what the one before wrote.
+            for bind_group in &bind_groups.steps {
+                pass.set_bind_group(0, bind_group, &[]);
+                pass.dispatch_workgroups(workgroups, workgroups, 1);
             }
         }
+
+        // Copy the newest state to the texture that is read back.
+        let images = world.resource::<SimImages>();
+        let gpu_images = world.resource::<RenderAssets<GpuImage>>();
+        let last = [&images.texture_a, &images.texture_b][bind_groups.last];
+        let (Some(last), Some(out)) = (gpu_images.get(last), gpu_images.get(&images.texture_out))
+        else {
+            return Ok(());
+        };
+        render_context.command_encoder().copy_texture_to_texture(
+            last.texture.as_image_copy(),
+            out.texture.as_image_copy(),
+            last.size,
+        );
         Ok(())
     }
 }
@@ -11,19 +11,57 @@ use bevy::{
         render_resource::{
             BindGroup, BindGroupEntries, BindGroupLayout, BindGroupLayoutEntries,
             CachedComputePipelineId, ComputePipelineDescriptor, PipelineCache, ShaderStages,
-            StorageTextureAccess, TextureFormat, binding_types::texture_storage_2d,
+            ShaderType, StorageTextureAccess, TextureFormat, UniformBuffer,
+            binding_types::{texture_storage_2d, uniform_buffer},
         },
-        renderer::RenderDevice,
+        renderer::{RenderDevice, RenderQueue},
         texture::GpuImage,
     },
 };
 
-use crate::sim::data::*;
+use crate::sim::{NUM_COUNTS, data::*, render::gpu::GpuSteps};
+
+/// Mirrors `SimParams` in the shader.
+#[derive(ShaderType, Clone, Copy, Debug)]
+pub struct SimParams {
+    /// Flattened [`crate::sim::TransitionTable`],
+    /// indexed by `condition * NUM_COUNTS + num_active`.
+    /// `x` is the chance to become active, `y` the chance to become empty.
+    pub table: [Vec4; 4 * NUM_COUNTS],
+    /// The current player's team, for telling owned cells from enemy cells.
+    pub team: u32,
+    pub generation: u32,
+    pub seed: u32,
+    /// [`BoundaryMode`] discriminant.
+    pub boundary: u32,
+    /// [`crate::sim::Neighborhood::kind`].
+    pub neighborhood: u32,
+    pub radius: u32,
+    /// Number of dying stages, see [`crate::sim::TransitionTable::stages`].
+    pub stages: u32,
+}
+impl SimParams {
+    pub fn new(settings: &SimSettings, gameplay: &SimGameplayState, generation: u32) -> Self {
+        let mut table = [Vec4::ZERO; 4 * NUM_COUNTS];
+        for (i, transition) in settings.rule.table.iter().flatten().enumerate() {
+            table[i] = Vec4::new(transition.active, transition.empty, 0., 0.);
+        }
+        Self {
+            table,
+            team: gameplay.sim_team(settings) as u32,
+            generation,
+            seed: settings.seed.0,
+            boundary: settings.boundary as u32,
+            neighborhood: settings.neighborhood.kind(),
+            radius: settings.neighborhood.radius(),
+            stages: settings.rule.stages as u32,
+        }
+    }
+}
 
 #[derive(Resource)]
 pub struct SimPipeline {
     pub texture_bind_group_layout: BindGroupLayout,
-    pub init_pipeline: CachedComputePipelineId,
     pub update_pipeline: CachedComputePipelineId,
 }
 
@@ -37,20 +75,12 @@ impl FromWorld for SimPipeline {
                 (
                     texture_storage_2d(TextureFormat::Rgba8Unorm, StorageTextureAccess::ReadOnly),
                     texture_storage_2d(TextureFormat::Rgba8Unorm, StorageTextureAccess::WriteOnly),
+                    uniform_buffer::<SimParams>(false),
                 ),
             ),
         );
         let shader = world.load_asset(SHADER_ASSET_PATH);
         let pipeline_cache = world.resource::<PipelineCache>();
-        let init_pipeline = pipeline_cache.queue_compute_pipeline(ComputePipelineDescriptor {
-            label: None,
-            layout: vec![texture_bind_group_layout.clone()],
-            push_constant_ranges: Vec::new(),
-            shader: shader.clone(),
-            shader_defs: vec![],
-            entry_point: Cow::from("init"),
-            zero_initialize_workgroup_memory: true,
-        });
         let update_pipeline = pipeline_cache.queue_compute_pipeline(ComputePipelineDescriptor {
             label: None,
             layout: vec![texture_bind_group_layout.clone()],
@@ -62,34 +92,54 @@ impl FromWorld for SimPipeline {
         });
 
         SimPipeline {
-            init_pipeline,
             texture_bind_group_layout,
             update_pipeline,
         }
     }
 }
 
-#[derive(Resource)]
-pub struct SimBindGroups(pub [BindGroup; 2]);
+/// One bind group per step of [`GpuSteps`], in order.
+/// `last` is the buffer written by the final step.
+#[derive(Resource, Default)]
+pub struct SimBindGroups {
+    pub steps: Vec<BindGroup>,
+    pub last: usize,
+}
 
 pub fn prepare_bind_group(
     mut commands: Commands,
     pipeline: Res<SimPipeline>,
     gpu_images: Res<RenderAssets<GpuImage>>,
     images: Res<SimImages>,
+    settings: Res<SimSettings>,
+    gameplay: Res<SimGameplayState>,
+    steps: Res<GpuSteps>,
     render_device: Res<RenderDevice>,
+    render_queue: Res<RenderQueue>,
 ) {
-    let view_a = gpu_images.get(&images.texture_a).unwrap();
-    let view_b = gpu_images.get(&images.texture_b).unwrap();
-    let bind_group_0 = render_device.create_bind_group(
-        None,
-        &pipeline.texture_bind_group_layout,
-        &BindGroupEntries::sequential((&view_a.texture_view, &view_b.texture_view)),
-    );
-    let bind_group_1 = render_device.create_bind_group(
-        None,
-        &pipeline.texture_bind_group_layout,
-        &BindGroupEntries::sequential((&view_b.texture_view, &view_a.texture_view)),
-    );
-    commands.insert_resource(SimBindGroups([bind_group_0, bind_group_1]));
+    let buffers = [
+        gpu_images.get(&images.texture_a).unwrap(),
+        gpu_images.get(&images.texture_b).unwrap(),
+    ];
+    let bind_groups = steps
+        .generations()
+        .map(|generation| {
+            let mut params = UniformBuffer::from(SimParams::new(&settings, &gameplay, generation));
+            params.write_buffer(&render_device, &render_queue);
+            let (input, output) = (generation as usize % 2, (generation as usize + 1) % 2);
+            render_device.create_bind_group(
+                None,
+                &pipeline.texture_bind_group_layout,
+                &BindGroupEntries::sequential((
+                    &buffers[input].texture_view,
+                    &buffers[output].texture_view,
+                    params.binding().unwrap(),
+                )),
+            )
+        })
+        .collect();
+    commands.insert_resource(SimBindGroups {
+        steps: bind_groups,
+        last: steps.to as usize % 2,
+    });
 }
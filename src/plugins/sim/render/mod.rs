@@ -1,3 +1,4 @@
+pub mod colorize;
 pub mod cpu;
 #[cfg(not(target_arch = "wasm32"))]
 pub mod gpu;
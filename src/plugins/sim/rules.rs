@@ -0,0 +1,439 @@
+//! Cell rules decide what a cell becomes on the next step.
+//! The stepping kernels only know how to gather a neighborhood; the rule
+//! itself is looked up per team so new seed behaviors can be added here.
+
+use std::{fmt::Display, str::FromStr, sync::Arc};
+
+use bevy::{platform::collections::HashMap, prelude::*};
+use thiserror::Error;
+
+use crate::sim::{
+    MAX_NEIGHBORS,
+    data::{CellCondition, CellResult, TeamID},
+};
+
+/// Number of possible active neighbor counts, `0..=MAX_NEIGHBORS`.
+pub const NUM_COUNTS: usize = MAX_NEIGHBORS + 1;
+
+/// A transition function for a single cell.
+/// `neighborhood` holds one entry per neighbor of the current
+/// [`crate::sim::Neighborhood`], so its length depends on the setting.
+/// `roll` is uniform in `[0, 1)` and drawn fresh for every cell on every step;
+/// deterministic rules ignore it.
+pub trait CellRule: std::fmt::Debug + Send + Sync {
+    fn calculate(
+        &self,
+        current: CellCondition,
+        neighborhood: &[CellCondition],
+        roll: f32,
+    ) -> CellResult;
+}
+
+/// The original hard-coded rule.
+/// Any cell with 2 or 3 active neighbors becomes active, active cells outside
+/// that range die, and everything else is left alone.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub struct ConwayRule;
+impl CellRule for ConwayRule {
+    fn calculate(
+        &self,
+        current: CellCondition,
+        neighborhood: &[CellCondition],
+        _roll: f32,
+    ) -> CellResult {
+        let num_active = neighborhood
+            .iter()
+            .filter(|c| **c == CellCondition::Active)
+            .count();
+        let spawn = current == CellCondition::Empty && num_active == 3;
+        let stay_alive = num_active == 2 || num_active == 3;
+        let die = current == CellCondition::Active && !(2..=3).contains(&num_active);
+
+        if spawn || stay_alive {
+            CellResult::Active
+        } else if die {
+            CellResult::Empty
+        } else {
+            CellResult::Untouched
+        }
+    }
+}
+
+/// A Life-like rule in B/S notation, e.g. `B3/S23`.
+/// Bit `n` of each mask is set when `n` active neighbors trigger it.
+/// Empty, owned and enemy cells are born; only active cells survive.
+/// With `stages` set this is a Generations rule, e.g. `B2/S/C4`:
+/// cells which die pass through that many dying stages before becoming empty.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct LifeRule {
+    pub birth: u64,
+    pub survive: u64,
+    pub stages: u8,
+}
+impl Default for LifeRule {
+    /// Conway's Game of Life.
+    fn default() -> Self {
+        Self {
+            birth: 1 << 3,
+            survive: (1 << 2) | (1 << 3),
+            stages: 0,
+        }
+    }
+}
+
+/// What an active cell becomes when it dies.
+fn die(stages: u8) -> CellResult {
+    if stages == 0 {
+        CellResult::Empty
+    } else {
+        CellResult::Dying(1)
+    }
+}
+
+/// Dying cells ignore their neighbors and move one stage along each step.
+fn decay(stage: u8, stages: u8) -> CellResult {
+    if stage >= stages {
+        CellResult::Empty
+    } else {
+        CellResult::Dying(stage + 1)
+    }
+}
+impl LifeRule {
+    pub fn births(&self, num_active: usize) -> bool {
+        num_active < NUM_COUNTS && self.birth & (1 << num_active) != 0
+    }
+    pub fn survives(&self, num_active: usize) -> bool {
+        num_active < NUM_COUNTS && self.survive & (1 << num_active) != 0
+    }
+}
+impl CellRule for LifeRule {
+    fn calculate(
+        &self,
+        current: CellCondition,
+        neighborhood: &[CellCondition],
+        _roll: f32,
+    ) -> CellResult {
+        let num_active = neighborhood
+            .iter()
+            .filter(|c| **c == CellCondition::Active)
+            .count();
+        match current {
+            CellCondition::Dying(stage) => decay(stage, self.stages),
+            CellCondition::Active if self.survives(num_active) => CellResult::Active,
+            CellCondition::Active => die(self.stages),
+            _ if self.births(num_active) => CellResult::Active,
+            _ => CellResult::Untouched,
+        }
+    }
+}
+
+/// Transition probabilities for one (condition, neighbor count) pair.
+/// Whatever is left over leaves the cell untouched.
+#[derive(Debug, Default, Copy, Clone, PartialEq)]
+pub struct Transition {
+    pub active: f32,
+    pub empty: f32,
+}
+impl Transition {
+    pub fn sample(&self, roll: f32) -> CellResult {
+        if roll < self.active {
+            CellResult::Active
+        } else if roll < self.active + self.empty {
+            CellResult::Empty
+        } else {
+            CellResult::Untouched
+        }
+    }
+}
+
+/// Probabilistic rule indexed by the current [`CellCondition`] and the
+/// number of active neighbors. As in Generations rules, dying neighbors
+/// don't count towards it.
+/// This is what the compute shader runs, so every rule used as
+/// [`crate::sim::SimSettings::rule`] must be expressible as a table.
+/// Dying cells decay through `stages` as in [`LifeRule`] and skip the table.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct TransitionTable {
+    pub table: [[Transition; NUM_COUNTS]; 4],
+    pub stages: u8,
+}
+impl Default for TransitionTable {
+    fn default() -> Self {
+        LifeRule::default().into()
+    }
+}
+impl TransitionTable {
+    /// Dying and dormant cells have no row and are always left untouched.
+    pub fn get(&self, current: CellCondition, num_active: usize) -> Transition {
+        let row = match current {
+            CellCondition::Empty => 0,
+            CellCondition::Active => 1,
+            CellCondition::Owned => 2,
+            CellCondition::Enemy => 3,
+            CellCondition::Dying(_) | CellCondition::Dormant => return Transition::default(),
+        };
+        self.table[row][num_active.min(MAX_NEIGHBORS)]
+    }
+    /// Builds a table from per-count birth and survival probabilities.
+    fn from_probabilities(
+        birth: [f32; NUM_COUNTS],
+        survive: [f32; NUM_COUNTS],
+        stages: u8,
+    ) -> Self {
+        let born = birth.map(|p| Transition {
+            active: p,
+            empty: 0.,
+        });
+        let survived = survive.map(|p| Transition {
+            active: p,
+            empty: 1. - p,
+        });
+        Self {
+            table: [born, survived, born, born],
+            stages,
+        }
+    }
+}
+impl From<LifeRule> for TransitionTable {
+    fn from(rule: LifeRule) -> Self {
+        let probabilities =
+            |mask: u64| std::array::from_fn(|n| (mask & (1 << n) != 0) as u8 as f32);
+        Self::from_probabilities(
+            probabilities(rule.birth),
+            probabilities(rule.survive),
+            rule.stages,
+        )
+    }
+}
+impl CellRule for TransitionTable {
+    fn calculate(
+        &self,
+        current: CellCondition,
+        neighborhood: &[CellCondition],
+        roll: f32,
+    ) -> CellResult {
+        let num_active = neighborhood
+            .iter()
+            .filter(|c| **c == CellCondition::Active)
+            .count();
+        match (current, self.get(current, num_active).sample(roll)) {
+            (CellCondition::Dying(stage), _) => decay(stage, self.stages),
+            (CellCondition::Active, CellResult::Empty) => die(self.stages),
+            (_, result) => result,
+        }
+    }
+}
+
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum RuleParseError {
+    #[error("expected two sections separated by '/', e.g. B3/S23")]
+    MissingSeparator,
+    #[error("missing the 'B' section with the birth counts")]
+    MissingBirth,
+    #[error("missing the 'S' section with the survival counts")]
+    MissingSurvival,
+    #[error("section '{0}' must start with 'B', 'S' or 'C'")]
+    MissingPrefix(String),
+    #[error("'{0}' is not a number of states (2-255)")]
+    InvalidStates(String),
+    #[error("'{0}' appears more than once")]
+    DuplicateSection(char),
+    #[error("'{0}' is not a neighbor count (0-48)")]
+    InvalidCount(String),
+    #[error("'{0}' is not a probability between 0 and 1")]
+    InvalidProbability(String),
+    #[error("probabilities are not allowed in a deterministic rule")]
+    Probabilistic,
+}
+
+/// Birth and survival probabilities per count, plus the number of dying stages.
+struct ParsedRule {
+    birth: [f32; NUM_COUNTS],
+    survive: [f32; NUM_COUNTS],
+    stages: u8,
+}
+
+/// Parses B/S notation into per-count birth and survival probabilities.
+/// Each count may be followed by a probability in parentheses,
+/// e.g. `B3(0.5)/S23` births on 3 neighbors half of the time.
+/// Counts above 9 need comma separated counts or ranges,
+/// e.g. `B10..14/S8..18` or `B3,10(0.5)/S2,3`.
+/// An optional `C` section gives the total number of states for
+/// Generations rules, e.g. `B2/S/C4` has alive, dead and two dying states.
+fn parse_sections(s: &str) -> Result<ParsedRule, RuleParseError> {
+    let sections = s.trim().split('/').collect::<Vec<_>>();
+    if sections.len() < 2 {
+        return Err(RuleParseError::MissingSeparator);
+    }
+    let mut birth = None;
+    let mut survive = None;
+    let mut stages = None;
+    for section in sections {
+        let section = section.trim();
+        let mut chars = section.chars();
+        let prefix = chars.next().map(|c| c.to_ascii_uppercase());
+        let counts = chars.as_str();
+        let duplicate = match prefix {
+            Some('B') => birth.replace(parse_counts(counts)?).is_some(),
+            Some('S') => survive.replace(parse_counts(counts)?).is_some(),
+            Some('C') => {
+                let states = counts
+                    .trim()
+                    .parse::<u8>()
+                    .ok()
+                    .filter(|n| *n >= 2)
+                    .ok_or(RuleParseError::InvalidStates(counts.to_owned()))?;
+                stages.replace(states - 2).is_some()
+            }
+            _ => return Err(RuleParseError::MissingPrefix(section.to_owned())),
+        };
+        if duplicate {
+            return Err(RuleParseError::DuplicateSection(prefix.unwrap_or_default()));
+        }
+    }
+    Ok(ParsedRule {
+        birth: birth.ok_or(RuleParseError::MissingBirth)?,
+        survive: survive.ok_or(RuleParseError::MissingSurvival)?,
+        stages: stages.unwrap_or_default(),
+    })
+}
+
+fn parse_counts(counts: &str) -> Result<[f32; NUM_COUNTS], RuleParseError> {
+    if counts.contains(',') || counts.contains("..") {
+        parse_extended_counts(counts)
+    } else {
+        parse_digit_counts(counts)
+    }
+}
+
+fn parse_probability(p: &str) -> Result<f32, RuleParseError> {
+    p.trim()
+        .parse::<f32>()
+        .ok()
+        .filter(|p| (0.0..=1.0).contains(p))
+        .ok_or(RuleParseError::InvalidProbability(p.to_owned()))
+}
+
+/// Classic notation, one digit per count: `23` or `3(0.5)`.
+fn parse_digit_counts(counts: &str) -> Result<[f32; NUM_COUNTS], RuleParseError> {
+    let mut probabilities = [0.; NUM_COUNTS];
+    let mut chars = counts.chars().peekable();
+    while let Some(c) = chars.next() {
+        let n = match c.to_digit(10) {
+            Some(n @ 0..=8) => n as usize,
+            _ => return Err(RuleParseError::InvalidCount(c.to_string())),
+        };
+        probabilities[n] = 1.;
+        if chars.next_if_eq(&'(').is_some() {
+            let p = chars.by_ref().take_while(|c| *c != ')').collect::<String>();
+            probabilities[n] = parse_probability(&p)?;
+        }
+    }
+    Ok(probabilities)
+}
+
+/// Comma separated counts or inclusive ranges: `3,10(0.5)` or `34..45`.
+fn parse_extended_counts(counts: &str) -> Result<[f32; NUM_COUNTS], RuleParseError> {
+    let mut probabilities = [0.; NUM_COUNTS];
+    for item in counts
+        .split(',')
+        .map(str::trim)
+        .filter(|item| !item.is_empty())
+    {
+        let (range, p) = match item.strip_suffix(')').and_then(|item| item.split_once('(')) {
+            Some((range, p)) => (range, parse_probability(p)?),
+            None => (item, 1.),
+        };
+        let count = |n: &str| {
+            n.trim()
+                .parse::<usize>()
+                .ok()
+                .filter(|n| *n <= MAX_NEIGHBORS)
+                .ok_or(RuleParseError::InvalidCount(n.to_owned()))
+        };
+        let (start, end) = match range.split_once("..") {
+            Some((start, end)) => (count(start)?, count(end)?),
+            None => (count(range)?, count(range)?),
+        };
+        for probability in probabilities.iter_mut().take(end + 1).skip(start) {
+            *probability = p;
+        }
+    }
+    Ok(probabilities)
+}
+
+impl FromStr for LifeRule {
+    type Err = RuleParseError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let rule = parse_sections(s)?;
+        let mask = |probabilities: [f32; NUM_COUNTS]| {
+            probabilities
+                .into_iter()
+                .enumerate()
+                .try_fold(0u64, |mask, (n, p)| match p {
+                    0. => Ok(mask),
+                    1. => Ok(mask | (1 << n)),
+                    _ => Err(RuleParseError::Probabilistic),
+                })
+        };
+        Ok(Self {
+            birth: mask(rule.birth)?,
+            survive: mask(rule.survive)?,
+            stages: rule.stages,
+        })
+    }
+}
+
+impl FromStr for TransitionTable {
+    type Err = RuleParseError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let rule = parse_sections(s)?;
+        Ok(Self::from_probabilities(
+            rule.birth,
+            rule.survive,
+            rule.stages,
+        ))
+    }
+}
+
+impl Display for LifeRule {
+    /// Uses the classic digit notation unless a count above 8 is set.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let extended = (self.birth | self.survive) >> 9 != 0;
+        let counts = |mask: u64| {
+            let counts = (0..NUM_COUNTS).filter(|n| mask & (1 << n) != 0);
+            if extended {
+                counts.map(|n| n.to_string()).collect::<Vec<_>>().join(",")
+            } else {
+                counts.map(|n| n.to_string()).collect::<String>()
+            }
+        };
+        write!(f, "B{}/S{}", counts(self.birth), counts(self.survive))?;
+        if self.stages > 0 {
+            write!(f, "/C{}", self.stages as u16 + 2)?;
+        }
+        Ok(())
+    }
+}
+
+/// Registered rules. Teams without an entry use [`SimRules::default`].
+/// Simultaneous rounds always run the default, see
+/// [`crate::sim::SimGameplayState::sim_team`].
+#[derive(Resource, Clone, Debug)]
+pub struct SimRules {
+    pub default: Arc<dyn CellRule>,
+    pub teams: HashMap<TeamID, Arc<dyn CellRule>>,
+}
+impl Default for SimRules {
+    fn default() -> Self {
+        Self {
+            default: Arc::new(ConwayRule),
+            teams: HashMap::default(),
+        }
+    }
+}
+impl SimRules {
+    pub fn get(&self, team: TeamID) -> Arc<dyn CellRule> {
+        self.teams.get(&team).unwrap_or(&self.default).clone()
+    }
+}
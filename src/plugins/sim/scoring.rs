@@ -0,0 +1,50 @@
+//! Territory rankings for the end of a match.
+
+use crate::sim::{
+    TerritoryCount,
+    data::{Team, TeamID},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TeamScore {
+    pub team: TeamID,
+    /// Captured cells held by the team.
+    pub cells: usize,
+    /// `cells` as a fraction of the whole board.
+    pub share: f32,
+    /// 1 for the winners. Tied teams share a rank and the next rank is
+    /// skipped, e.g. 1, 1, 3.
+    pub rank: usize,
+}
+
+/// Ranks the teams by captured cells, best first.
+pub fn score(count: &TerritoryCount, teams: &[Team]) -> Vec<TeamScore> {
+    let mut scores = teams
+        .iter()
+        .map(|team| {
+            let cells = count.owned.get(team.id).copied().unwrap_or(0);
+            TeamScore {
+                team: team.id,
+                cells,
+                share: count.share(cells),
+                rank: 0,
+            }
+        })
+        .collect::<Vec<_>>();
+    scores.sort_by(|a, b| b.cells.cmp(&a.cells));
+    let mut rank = 0;
+    let mut previous = None;
+    for (i, score) in scores.iter_mut().enumerate() {
+        if previous != Some(score.cells) {
+            rank = i + 1;
+            previous = Some(score.cells);
+        }
+        score.rank = rank;
+    }
+    scores
+}
+
+/// Every team in first place. More than one means a tie.
+pub fn winners(scores: &[TeamScore]) -> impl Iterator<Item = &TeamScore> {
+    scores.iter().filter(|score| score.rank == 1)
+}
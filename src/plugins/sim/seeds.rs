@@ -0,0 +1,130 @@
+//! Dormant seeds. A seed sits on a single cell until its wake condition is
+//! met, then expands into its stamp. Any team's active cells can wake it.
+
+use bevy::prelude::*;
+
+use crate::sim::{
+    Cell, CellGrid, CellKind, SimSettings, SimState,
+    data::{BoundaryMode, TeamID},
+    lifecycle::update,
+    render::cpu::SoftwareSimSet,
+};
+
+/// Which of the 8 surrounding cells must be active for a seed to wake.
+/// Bit `(y + 1) * 3 + (x + 1)` is set for a required offset `(x, y)`,
+/// so the mask reads row by row like the 3x3 grid shown to players.
+/// The center bit is ignored and an empty mask never wakes.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, Hash, Reflect)]
+pub struct WakeMask(pub u16);
+impl WakeMask {
+    pub const CENTER: usize = 4;
+    pub fn bit(offset: IVec2) -> usize {
+        ((offset.y + 1) * 3 + offset.x + 1) as usize
+    }
+    pub fn is_set(&self, index: usize) -> bool {
+        index != Self::CENTER && self.0 & (1 << index) != 0
+    }
+    pub fn toggle(&mut self, index: usize) {
+        if index < 9 && index != Self::CENTER {
+            self.0 ^= 1 << index;
+        }
+    }
+    pub fn is_empty(&self) -> bool {
+        (0..9).all(|index| !self.is_set(index))
+    }
+    pub fn offsets(&self) -> impl Iterator<Item = IVec2> {
+        (-1..=1)
+            .flat_map(|y| (-1..=1).map(move |x| IVec2::new(x, y)))
+            .filter(|offset| self.is_set(Self::bit(*offset)))
+    }
+    /// Whether every required neighbor of `pos` is active.
+    pub fn is_met(&self, grid: &CellGrid, pos: UVec2, boundary: BoundaryMode) -> bool {
+        !self.is_empty()
+            && self.offsets().all(|offset| {
+                grid.get_bounded(pos.as_ivec2() + offset, boundary).kind == CellKind::Active
+            })
+    }
+}
+
+/// Stored in [`CellGrid::seeds`] and referenced by the dormant cell's state.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DormantSeed {
+    /// Cells activated on waking, relative to the seed cell.
+    pub pattern: Vec<IVec2>,
+    pub mask: WakeMask,
+    pub owner: TeamID,
+    pub awake: bool,
+}
+
+/// Raised once per seed when its wake condition is first met.
+#[derive(Event, Debug, Copy, Clone)]
+pub struct SeedActivatedEvent {
+    pub seed: usize,
+    pub owner: TeamID,
+    pub position: UVec2,
+}
+
+pub struct SeedPlugin;
+impl Plugin for SeedPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            FixedUpdate,
+            wake_seeds
+                .run_if(in_state(SimState::Running))
+                .after(SoftwareSimSet)
+                .before(update),
+        )
+        .add_observer(log_activation);
+    }
+}
+
+fn log_activation(trigger: Trigger<SeedActivatedEvent>) {
+    let event = trigger.event();
+    info!(
+        "Seed {} of team {} woke at {}",
+        event.seed, event.owner, event.position
+    );
+}
+
+/// Expands every dormant seed whose wake condition is met. Each seed wakes
+/// once: whether it has is kept in [`CellGrid::seeds`], which never leaves
+/// the CPU.
+pub fn wake_seeds(mut commands: Commands, mut grid: ResMut<CellGrid>, settings: Res<SimSettings>) {
+    let woken = grid
+        .cells
+        .iter()
+        .enumerate()
+        .filter(|(_, cell)| cell.kind == CellKind::Dormant)
+        .filter_map(|(i, cell)| {
+            let pos = grid.position(i);
+            let seed = grid.seeds.get(cell.state as usize)?;
+            (!seed.awake && seed.mask.is_met(&grid, pos, settings.boundary))
+                .then_some((cell.state as usize, pos))
+        })
+        .collect::<Vec<_>>();
+    for (index, pos) in woken {
+        let seed = &mut grid.seeds[index];
+        seed.awake = true;
+        let (pattern, owner) = (seed.pattern.clone(), seed.owner);
+
+        grid.set(pos, Cell::EMPTY);
+        for offset in pattern {
+            // Other dormant seeds are left alone so they can be woken in turn.
+            if let Some(target) = settings
+                .boundary
+                .resolve(pos.as_ivec2() + offset, grid.size)
+                .filter(|target| {
+                    grid.get(*target)
+                        .is_some_and(|cell| cell.kind != CellKind::Dormant)
+                })
+            {
+                grid.set(target, Cell::ACTIVE.with_owner(Some(owner)));
+            }
+        }
+        commands.trigger(SeedActivatedEvent {
+            seed: index,
+            owner,
+            position: pos,
+        });
+    }
+}
@@ -0,0 +1,125 @@
+//! Cell counts of the board. Scoring and victory checks read from here.
+
+use bevy::prelude::*;
+
+use crate::sim::{
+    CellGrid, CellKind, SimSettings, SimState, commit_state, render::cpu::SoftwareSimSet,
+    wake_seeds,
+};
+
+/// Counts of one board.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TerritoryCount {
+    pub empty: usize,
+    pub active: usize,
+    pub dying: usize,
+    pub dormant: usize,
+    /// Captured cells per team.
+    pub owned: Vec<usize>,
+    /// Every cell a team still has on the board, so its territory as well as
+    /// its active, dying and dormant cells.
+    pub alive: Vec<usize>,
+    /// Every cell on the board.
+    pub total: usize,
+}
+impl TerritoryCount {
+    pub fn count(grid: &CellGrid, teams: usize) -> Self {
+        let mut count = Self {
+            owned: vec![0; teams],
+            alive: vec![0; teams],
+            total: grid.cells.len(),
+            ..Default::default()
+        };
+        for cell in &grid.cells {
+            match cell.kind {
+                CellKind::Empty => count.empty += 1,
+                CellKind::Active => count.active += 1,
+                CellKind::Dying => count.dying += 1,
+                CellKind::Dormant => count.dormant += 1,
+                CellKind::Captured => {
+                    if let Some(owned) = cell.owner.and_then(|team| count.owned.get_mut(team)) {
+                        *owned += 1;
+                    }
+                }
+            }
+            if let Some(alive) = cell.owner.and_then(|team| count.alive.get_mut(team)) {
+                *alive += 1;
+            }
+        }
+        count
+    }
+    /// `cells` as a fraction of the board.
+    pub fn share(&self, cells: usize) -> f32 {
+        cells as f32 / self.total.max(1) as f32
+    }
+}
+
+/// Live counts, updated on the fixed step whenever the grid changes.
+#[derive(Resource, Debug, Clone, Default)]
+pub struct TerritoryStats {
+    pub current: TerritoryCount,
+    /// Counts after every committed turn, oldest first.
+    pub history: Vec<TerritoryCount>,
+}
+impl TerritoryStats {
+    /// Change in `team`'s territory over the last committed turn.
+    pub fn last_change(&self, team: usize) -> Option<isize> {
+        let [.., before, after] = self.history.as_slice() else {
+            return None;
+        };
+        let owned = |count: &TerritoryCount| count.owned.get(team).copied().unwrap_or(0);
+        Some(owned(after) as isize - owned(before) as isize)
+    }
+}
+
+pub struct TerritoryStatsPlugin;
+impl Plugin for TerritoryStatsPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<TerritoryStats>()
+            .add_systems(OnEnter(SimState::Init), reset_stats)
+            .add_systems(
+                OnTransition {
+                    exited: SimState::Init,
+                    entered: SimState::Paused,
+                },
+                record_turn,
+            )
+            .add_systems(
+                OnTransition {
+                    exited: SimState::Running,
+                    entered: SimState::Paused,
+                },
+                record_turn.after(commit_state),
+            )
+            .add_systems(
+                FixedUpdate,
+                count_cells
+                    .after(SoftwareSimSet)
+                    .after(wake_seeds)
+                    .run_if(resource_exists_and_changed::<CellGrid>),
+            );
+    }
+}
+
+fn reset_stats(mut stats: ResMut<TerritoryStats>) {
+    *stats = TerritoryStats::default();
+}
+
+fn count_cells(grid: Res<CellGrid>, settings: Res<SimSettings>, mut stats: ResMut<TerritoryStats>) {
+    let count = TerritoryCount::count(&grid, settings.teams.len());
+    if stats.current != count {
+        stats.current = count;
+    }
+}
+
+/// Counts the freshly set up or committed board right away,
+/// so victory checks see this turn.
+pub fn record_turn(
+    grid: Res<CellGrid>,
+    settings: Res<SimSettings>,
+    mut stats: ResMut<TerritoryStats>,
+) {
+    let count = TerritoryCount::count(&grid, settings.teams.len());
+    stats.history.push(count.clone());
+    stats.current = count;
+}
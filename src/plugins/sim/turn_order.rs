@@ -0,0 +1,90 @@
+//! Who plays which turn. Turns are counted by [`crate::sim::SimGameplayState::turn`].
+
+use std::fmt::Display;
+
+use crate::sim::{Player, PlayerID, Team};
+
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum TurnOrder {
+    /// 1-2-3, 1-2-3.
+    #[default]
+    RoundRobin,
+    /// 1-2-3, 3-2-1, so nobody always goes first.
+    Snake,
+    /// Teams take turns in order and each team rotates through its players,
+    /// so smaller teams get as many turns as larger ones. Players who have
+    /// placed all their seeds sit out the rest of the match.
+    TeamAlternating,
+    /// Every player stages a placement in secret, then all of them are
+    /// revealed and simulated together.
+    Simultaneous,
+}
+impl TurnOrder {
+    /// The player placing on `turn`.
+    pub fn player(&self, turn: u32, players: &[Player], teams: &[Team]) -> PlayerID {
+        let count = players.len().max(1);
+        let turn = turn as usize;
+        let (round, index) = (turn / count, turn % count);
+        match self {
+            Self::RoundRobin | Self::Simultaneous => index,
+            Self::Snake if round % 2 == 0 => index,
+            Self::Snake => count - 1 - index,
+            Self::TeamAlternating => {
+                let teams = teams
+                    .iter()
+                    .filter(|team| !team.players.is_empty())
+                    .collect::<Vec<_>>();
+                if teams.is_empty() {
+                    return index;
+                }
+                let team = teams[turn % teams.len()];
+                team.players[(turn / teams.len()) % team.players.len()]
+            }
+        }
+    }
+    /// Turns until every player has had a turn for each of their seeds,
+    /// where `seeds[player]` is the number of seeds that player drafted.
+    /// Simultaneous matches always end on a full round.
+    pub fn total_turns(&self, players: &[Player], teams: &[Team], seeds: &[u32]) -> u32 {
+        let count = players.len().max(1) as u32;
+        let mut left = seeds.to_vec();
+        // Every player comes up at least once per `count * teams` turns, this
+        // only guards against teams that don't list their players.
+        let most = left.iter().copied().max().unwrap_or(0);
+        let limit = most * count * teams.len().max(1) as u32;
+        let mut turns = 0;
+        while turns < limit && left.iter().any(|seeds| *seeds > 0) {
+            let player = self.player(turns, players, teams);
+            if let Some(seeds) = left.get_mut(player) {
+                *seeds = seeds.saturating_sub(1);
+            }
+            turns += 1;
+        }
+        match self {
+            Self::Simultaneous => turns.next_multiple_of(count),
+            _ => turns,
+        }
+    }
+}
+impl Display for TurnOrder {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::RoundRobin => write!(f, "Round robin"),
+            Self::Snake => write!(f, "Snake"),
+            Self::TeamAlternating => write!(f, "Team alternating"),
+            Self::Simultaneous => write!(f, "Simultaneous"),
+        }
+    }
+}
+impl TryFrom<&String> for TurnOrder {
+    type Error = anyhow::Error;
+    fn try_from(value: &String) -> anyhow::Result<Self> {
+        match value.as_str() {
+            "Round robin" => Ok(Self::RoundRobin),
+            "Snake" => Ok(Self::Snake),
+            "Team alternating" => Ok(Self::TeamAlternating),
+            "Simultaneous" => Ok(Self::Simultaneous),
+            _ => Err(anyhow::anyhow!("No such turn order")),
+        }
+    }
+}
@@ -0,0 +1,198 @@
+//! How a match is won. Checked after every committed turn.
+
+use std::fmt::Display;
+
+use bevy::prelude::*;
+use itertools::Itertools;
+
+use crate::sim::{
+    CellGrid, CellKind, SimGameplayState, SimSettings, SimState, TerritoryStats, TurnOrder,
+    data::TeamID, record_turn, score, winners,
+};
+
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum VictoryCondition {
+    /// Most territory after [`SimSettings::rounds`] rounds, or once every
+    /// player has placed all their seeds.
+    #[default]
+    MostTerritory,
+    /// First team to hold `victory_share` of the board.
+    Threshold,
+    /// A team without any cells left is out. The last team standing wins.
+    Elimination,
+    /// Hold most of the [`hill_zones`] for a full round.
+    KingOfTheHill,
+}
+impl Display for VictoryCondition {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::MostTerritory => write!(f, "Most territory"),
+            Self::Threshold => write!(f, "Board share"),
+            Self::Elimination => write!(f, "Elimination"),
+            Self::KingOfTheHill => write!(f, "King of the hill"),
+        }
+    }
+}
+impl TryFrom<&String> for VictoryCondition {
+    type Error = anyhow::Error;
+    fn try_from(value: &String) -> anyhow::Result<Self> {
+        match value.as_str() {
+            "Most territory" => Ok(Self::MostTerritory),
+            "Board share" => Ok(Self::Threshold),
+            "Elimination" => Ok(Self::Elimination),
+            "King of the hill" => Ok(Self::KingOfTheHill),
+            _ => Err(anyhow::anyhow!("No such victory condition")),
+        }
+    }
+}
+
+/// The marked zones of a king of the hill match: three squares along the
+/// diagonal, so split layouts give each side one zone and leave the middle
+/// one contested.
+pub fn hill_zones(size: UVec2) -> Vec<URect> {
+    let half = (size.min_element() / 16).max(1);
+    [1, 2, 3]
+        .map(|quarter| URect::from_center_half_size(size * quarter / 4, UVec2::splat(half)))
+        .to_vec()
+}
+
+/// Zones to mark on the board. Only a king of the hill match has any.
+pub fn marked_zones(
+    settings: &SimSettings,
+    gameplay: &SimGameplayState,
+    size: UVec2,
+) -> Vec<URect> {
+    match settings.victory {
+        VictoryCondition::KingOfTheHill if gameplay.in_match => hill_zones(size),
+        _ => vec![],
+    }
+}
+
+/// The team with strictly the most captured cells in `zone`.
+fn zone_owner(grid: &CellGrid, zone: URect, teams: usize) -> Option<TeamID> {
+    let mut counts = vec![0; teams];
+    for y in zone.min.y..=zone.max.y {
+        for x in zone.min.x..=zone.max.x {
+            let owner = grid
+                .get(UVec2::new(x, y))
+                .filter(|cell| cell.kind == CellKind::Captured)
+                .and_then(|cell| cell.owner);
+            if let Some(count) = owner.and_then(|team| counts.get_mut(team)) {
+                *count += 1;
+            }
+        }
+    }
+    let best = counts.iter().copied().max().filter(|best| *best > 0)?;
+    let mut leaders = counts.iter().positions(|count| *count == best);
+    let leader = leaders.next()?;
+    leaders.next().is_none().then_some(leader)
+}
+
+/// How the match stands. Reset whenever a board is set up.
+#[derive(Resource, Debug, Clone, Default)]
+pub struct MatchStatus {
+    /// Teams knocked out in an elimination match. Their players pass.
+    pub eliminated: Vec<TeamID>,
+    /// The team holding most hills, and for how many turns in a row.
+    pub hill: Option<(TeamID, u32)>,
+    /// Set once the match is decided. Several teams means a tie.
+    pub winners: Option<Vec<TeamID>>,
+}
+
+pub struct VictoryPlugin;
+impl Plugin for VictoryPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<MatchStatus>()
+            .add_systems(OnEnter(SimState::Init), reset_status)
+            .add_systems(
+                OnTransition {
+                    exited: SimState::Running,
+                    entered: SimState::Paused,
+                },
+                check_victory
+                    .after(record_turn)
+                    .run_if(|gameplay: Res<SimGameplayState>| gameplay.in_match),
+            );
+    }
+}
+
+fn reset_status(mut status: ResMut<MatchStatus>) {
+    *status = MatchStatus::default();
+}
+
+pub fn check_victory(
+    grid: Res<CellGrid>,
+    stats: Res<TerritoryStats>,
+    settings: Res<SimSettings>,
+    gameplay: Res<SimGameplayState>,
+    mut status: ResMut<MatchStatus>,
+) {
+    if status.winners.is_some() {
+        return;
+    }
+    let teams = settings.teams.len();
+    let scores = score(&stats.current, &settings.teams);
+    let winners = match settings.victory {
+        VictoryCondition::MostTerritory => {
+            let turns = settings.rounds * settings.players.len() as u32;
+            (gameplay.turn >= turns).then(|| winners(&scores).map(|score| score.team).collect())
+        }
+        VictoryCondition::Threshold => {
+            let leaders = scores
+                .iter()
+                .filter(|score| score.rank == 1 && score.share >= settings.victory_share)
+                .map(|score| score.team)
+                .collect::<Vec<_>>();
+            (!leaders.is_empty()).then_some(leaders)
+        }
+        VictoryCondition::Elimination => {
+            // Everyone gets a first turn before anyone can be knocked out.
+            if (gameplay.turn as usize) < settings.players.len() {
+                return;
+            }
+            let remaining = scores
+                .iter()
+                .filter(|score| !status.eliminated.contains(&score.team))
+                .collect::<Vec<_>>();
+            let (out, standing): (Vec<_>, Vec<_>) = remaining
+                .iter()
+                .partition(|score| stats.current.alive.get(score.team).copied().unwrap_or(0) == 0);
+            status.eliminated.extend(out.iter().map(|score| score.team));
+            match standing.as_slice() {
+                // Everyone left went out together, so they tie.
+                [] => Some(out.iter().map(|score| score.team).collect()),
+                [last] => Some(vec![last.team]),
+                _ => None,
+            }
+        }
+        VictoryCondition::KingOfTheHill => {
+            let zones = hill_zones(grid.size);
+            let mut held = vec![0; teams];
+            for owner in zones
+                .iter()
+                .filter_map(|zone| zone_owner(&grid, *zone, teams))
+            {
+                held[owner] += 1;
+            }
+            let king = held.iter().position(|count| *count * 2 > zones.len());
+            status.hill = match (king, status.hill) {
+                (Some(king), Some((team, turns))) if king == team => Some((team, turns + 1)),
+                (Some(king), _) => Some((king, 1)),
+                (None, _) => None,
+            };
+            // Every other player gets a turn to take the hills back.
+            let round = match settings.turn_order {
+                TurnOrder::Simultaneous => 1,
+                _ => settings.players.len(),
+            };
+            status
+                .hill
+                .filter(|(_, turns)| *turns as usize > round)
+                .map(|(team, _)| vec![team])
+        }
+    };
+    if let Some(winners) = &winners {
+        info!("{} decided the match: {winners:?}", settings.victory);
+    }
+    status.winners = winners;
+}
@@ -0,0 +1,152 @@
+//! The user's own stamps, made in the stamp editor. They are saved as RLE
+//! files in the user's data folder, see [`library_dir`], and offered next to
+//! the catalog stamps. Names taken by the catalog can't be used.
+//! Browsers have no file system to save to, so the library is native only.
+
+use bevy::{
+    asset::io::{AssetSourceBuilder, AssetSourceId},
+    prelude::*,
+};
+
+use super::{Stamp, Stamps};
+use crate::sim::WakeMask;
+
+/// Asset source of the library, as in `user://glider.rle`.
+pub const LIBRARY_SOURCE: &str = "user";
+
+/// Where the library lives, e.g. `~/.local/share/markoff/stamps` on Linux.
+/// Fails if the platform has no user data folder.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn library_dir() -> anyhow::Result<std::path::PathBuf> {
+    let data = dirs::data_dir().ok_or_else(|| anyhow::anyhow!("there is no user data folder"))?;
+    Ok(data.join(env!("CARGO_PKG_NAME")).join("stamps"))
+}
+
+/// Makes the library loadable through [`LIBRARY_SOURCE`]. Must run before
+/// the asset plugin is added, and so before logging is set up.
+pub fn register_library_source(app: &mut App) {
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        let dir = match library_dir() {
+            Ok(dir) => dir,
+            Err(e) => {
+                eprintln!("The stamp library is unavailable: {e}");
+                return;
+            }
+        };
+        // The file watcher can't watch a folder that doesn't exist yet.
+        if let Err(e) = std::fs::create_dir_all(&dir) {
+            eprintln!(
+                "Could not create the stamp library at {}: {e}",
+                dir.display()
+            );
+        }
+        app.register_asset_source(
+            AssetSourceId::from(LIBRARY_SOURCE),
+            AssetSourceBuilder::platform_default(&dir.to_string_lossy(), None),
+        );
+    }
+    #[cfg(target_arch = "wasm32")]
+    let _ = app;
+}
+
+/// Whether a stamp at `path` comes from the library.
+pub fn is_library_path(path: &bevy::asset::AssetPath) -> bool {
+    *path.source() == AssetSourceId::from(LIBRARY_SOURCE)
+}
+
+/// Loads every pattern file in the library.
+pub(super) fn load_library(assets: Res<AssetServer>, mut stamps: ResMut<Stamps>) {
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        let Ok(entries) = library_dir().and_then(|dir| Ok(std::fs::read_dir(dir)?)) else {
+            return;
+        };
+        for entry in entries.flatten() {
+            let file_name = entry.file_name().to_string_lossy().into_owned();
+            if file_name.ends_with(".rle") || file_name.ends_with(".cells") {
+                let handle = assets.load(format!("{LIBRARY_SOURCE}://{file_name}"));
+                stamps.library.push(handle);
+            }
+        }
+    }
+    #[cfg(target_arch = "wasm32")]
+    let _ = (assets, stamps);
+}
+
+/// Library stamps are keyed by the name in their file, which is only known
+/// once they are loaded.
+pub(super) fn add_library_stamps(
+    mut events: EventReader<AssetEvent<Stamp>>,
+    mut stamps: ResMut<Stamps>,
+    stamp_assets: Res<Assets<Stamp>>,
+) {
+    let loaded = events.read().any(|event| {
+        let (AssetEvent::LoadedWithDependencies { id } | AssetEvent::Modified { id }) = event
+        else {
+            return false;
+        };
+        stamps.library.iter().any(|handle| handle.id() == *id)
+    });
+    if loaded {
+        stamps.add_library(&stamp_assets);
+    }
+}
+
+/// Saves a stamp to the library and loads it, replacing any library stamp
+/// saved under the same name. Fails if a catalog stamp has the name, or if
+/// another library stamp is saved under the same file name.
+pub fn save_to_library(
+    assets: &AssetServer,
+    stamps: &mut Stamps,
+    name: &str,
+    cells: &[UVec2],
+    cost: u32,
+    wake: Option<WakeMask>,
+) -> anyhow::Result<()> {
+    if name.trim().is_empty() {
+        anyhow::bail!("the stamp needs a name");
+    }
+    if cells.is_empty() {
+        anyhow::bail!("paint at least one cell");
+    }
+    if stamps.is_catalog_stamp(name.trim()) {
+        anyhow::bail!("a catalog stamp is already called '{}'", name.trim());
+    }
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        let file_name = name
+            .trim()
+            .chars()
+            .map(|c| {
+                if c.is_ascii_alphanumeric() {
+                    c.to_ascii_lowercase()
+                } else {
+                    '_'
+                }
+            })
+            .collect::<String>();
+        let file_name = format!("{file_name}.rle");
+        let dir = library_dir()?;
+        let file = dir.join(&file_name);
+        // Names differing only in case or punctuation share a file name.
+        if let Ok(text) = std::fs::read_to_string(&file) {
+            if super::loader::pattern_name(&text).as_deref() != Some(name.trim()) {
+                anyhow::bail!("another stamp is already saved as {file_name}");
+            }
+        }
+        std::fs::create_dir_all(&dir)?;
+        std::fs::write(&file, super::write_rle(name.trim(), cells, cost, wake))?;
+        let path = format!("{LIBRARY_SOURCE}://{file_name}");
+        match assets.get_handle::<Stamp>(&path) {
+            Some(_) => assets.reload(path),
+            None => stamps.library.push(assets.load(path)),
+        }
+        Ok(())
+    }
+    #[cfg(target_arch = "wasm32")]
+    {
+        let _ = (assets, stamps, cost, wake);
+        anyhow::bail!("saving stamps is not supported in the browser")
+    }
+}
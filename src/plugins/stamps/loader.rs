@@ -0,0 +1,392 @@
+//! Loads [`Stamp`]s from Life pattern files.
+//! Supported formats are RLE (`.rle`) and plaintext (`.cells`), see
+//! <https://conwaylife.com/wiki/Run_Length_Encoded> and
+//! <https://conwaylife.com/wiki/Plaintext>.
+//! The pattern is drawn into an image of its exact size, one pixel per cell,
+//! so that loaded stamps look and place like the ones cut from sprite sheets.
+//!
+//! RLE comments may also carry the stamp's cost and wake condition, as
+//! written by [`write_rle`]:
+//! ```text
+//! #C cost: 30
+//! #C wake: 0,1,2
+//! ```
+//! The wake condition lists the required neighbors of the 3x3 grid around
+//! the seed, row by row from the top left, see [`WakeMask`].
+
+use bevy::{
+    asset::{AssetLoader, LoadContext, RenderAssetUsages, io::Reader},
+    image::ImageSampler,
+    prelude::*,
+    render::render_resource::{Extent3d, TextureDimension, TextureFormat},
+};
+use thiserror::Error;
+
+use super::{Stamp, is_library_path};
+use crate::sim::WakeMask;
+
+#[derive(Error, Debug)]
+pub enum StampLoaderError {
+    #[error("could not read pattern: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("pattern is not valid UTF-8")]
+    Utf8(#[from] std::string::FromUtf8Error),
+    #[error("expected an RLE header like 'x = 3, y = 3', found '{0}'")]
+    InvalidHeader(String),
+    #[error("unexpected '{0}' in pattern")]
+    UnexpectedToken(char),
+    #[error("pattern has no live cells")]
+    Empty,
+}
+
+/// Live cells of a pattern file, as (column, row) from the top left.
+#[derive(Debug, Default)]
+struct Pattern {
+    name: Option<String>,
+    cells: Vec<UVec2>,
+    cost: Option<u32>,
+    wake: Option<WakeMask>,
+}
+impl Pattern {
+    fn size(&self) -> UVec2 {
+        self.cells
+            .iter()
+            .fold(UVec2::ZERO, |size, cell| size.max(cell + 1))
+    }
+}
+
+/// Reads the name, cost and wake condition out of an RLE comment line.
+/// Any other comment, or a cost or wake condition that doesn't parse, is
+/// just a comment.
+fn parse_comment(pattern: &mut Pattern, comment: &str) {
+    if let Some(name) = comment.strip_prefix('N') {
+        pattern.name = Some(name.trim().to_owned());
+    } else if let Some(cost) = comment.strip_prefix("C cost:") {
+        if let Ok(cost) = cost.trim().parse() {
+            pattern.cost = Some(cost);
+        }
+    } else if let Some(wake) = comment.strip_prefix("C wake:") {
+        let indices = wake
+            .split(',')
+            .map(str::trim)
+            .filter(|index| !index.is_empty())
+            .map(str::parse::<usize>)
+            .collect::<Result<Vec<_>, _>>();
+        if let Ok(indices) = indices {
+            let mut mask = WakeMask::default();
+            for index in indices {
+                mask.toggle(index);
+            }
+            pattern.wake = Some(mask).filter(|mask| !mask.is_empty());
+        }
+    }
+}
+
+fn parse_rle(text: &str) -> Result<Pattern, StampLoaderError> {
+    let mut pattern = Pattern::default();
+    let mut lines = text.lines().map(str::trim).filter(|line| !line.is_empty());
+    let header = loop {
+        let line = lines.next().ok_or(StampLoaderError::Empty)?;
+        match line.strip_prefix('#') {
+            Some(comment) => parse_comment(&mut pattern, comment),
+            None => break line,
+        }
+    };
+    let invalid_header = || StampLoaderError::InvalidHeader(header.to_owned());
+    // The rule comes last and may contain commas of its own, e.g. `:T100,100`.
+    // Stamps run under the board's rules, so it is skipped.
+    let size = match header.split_once("rule") {
+        Some((size, rule)) if rule.trim_start().starts_with('=') => size,
+        Some(_) => return Err(invalid_header()),
+        None => header,
+    };
+    let mut has_size = false;
+    for field in size.split(',').filter(|field| !field.trim().is_empty()) {
+        let (key, value) = field.split_once('=').ok_or_else(invalid_header)?;
+        if !matches!(key.trim(), "x" | "y") || value.trim().parse::<u32>().is_err() {
+            return Err(invalid_header());
+        }
+        has_size = true;
+    }
+    if !has_size {
+        return Err(invalid_header());
+    }
+
+    let mut position = UVec2::ZERO;
+    let mut run = 0;
+    'body: for line in lines {
+        for c in line.chars() {
+            let count = run.max(1);
+            match c {
+                '0'..='9' => {
+                    run = run * 10 + c.to_digit(10).unwrap_or_default();
+                    continue;
+                }
+                'b' | '.' => position.x += count,
+                '$' => position = UVec2::new(0, position.y + count),
+                '!' => break 'body,
+                // Multi-state patterns mark live cells with other letters.
+                'o' | 'A'..='Z' => {
+                    for _ in 0..count {
+                        pattern.cells.push(position);
+                        position.x += 1;
+                    }
+                }
+                c if c.is_whitespace() => {}
+                c => return Err(StampLoaderError::UnexpectedToken(c)),
+            }
+            run = 0;
+        }
+    }
+    Ok(pattern)
+}
+
+/// The name in an RLE file, if it has one and parses.
+pub(super) fn pattern_name(text: &str) -> Option<String> {
+    parse_rle(text).ok()?.name
+}
+
+fn parse_cells(text: &str) -> Result<Pattern, StampLoaderError> {
+    let mut pattern = Pattern::default();
+    let rows = text.lines().filter(|line| match line.strip_prefix('!') {
+        Some(comment) => {
+            if let Some(name) = comment.strip_prefix("Name:") {
+                pattern.name = Some(name.trim().to_owned());
+            }
+            false
+        }
+        None => true,
+    });
+    let mut cells = vec![];
+    for (y, row) in rows.enumerate() {
+        for (x, c) in row.trim_end().chars().enumerate() {
+            match c {
+                '.' => {}
+                'O' | '*' => cells.push(UVec2::new(x as u32, y as u32)),
+                c => return Err(StampLoaderError::UnexpectedToken(c)),
+            }
+        }
+    }
+    pattern.cells = cells;
+    Ok(pattern)
+}
+
+/// Encodes `cells` as RLE, readable by [`StampLoader`]. The pattern is
+/// trimmed to its bounding box.
+pub fn write_rle(name: &str, cells: &[UVec2], cost: u32, wake: Option<WakeMask>) -> String {
+    let min = cells.iter().copied().fold(UVec2::MAX, UVec2::min);
+    let max = cells.iter().copied().fold(UVec2::ZERO, UVec2::max);
+    let size = (max + 1).saturating_sub(min);
+    let mut out = format!("#N {name}\n#C cost: {cost}\n");
+    if let Some(mask) = wake.filter(|mask| !mask.is_empty()) {
+        let indices = (0..9)
+            .filter(|index| mask.is_set(*index))
+            .map(|index| index.to_string())
+            .collect::<Vec<_>>();
+        out += &format!("#C wake: {}\n", indices.join(","));
+    }
+    out += &format!("x = {}, y = {}\n", size.x, size.y);
+
+    // Runs of (count, tag), with trailing dead cells and empty rows folded
+    // into the row ends.
+    let mut runs: Vec<(u32, char)> = vec![];
+    let mut push = |count: u32, tag: char| match runs.last_mut() {
+        Some((n, last)) if *last == tag => *n += count,
+        _ => runs.push((count, tag)),
+    };
+    for y in 0..size.y {
+        let row = (0..size.x)
+            .map(|x| cells.contains(&(min + UVec2::new(x, y))))
+            .collect::<Vec<_>>();
+        let len = row.iter().rposition(|alive| *alive).map_or(0, |x| x + 1);
+        for alive in &row[..len] {
+            push(1, if *alive { 'o' } else { 'b' });
+        }
+        if y + 1 < size.y {
+            push(1, '$');
+        }
+    }
+    push(1, '!');
+
+    let mut line = String::new();
+    for (count, tag) in runs {
+        let run = match count {
+            1 => tag.to_string(),
+            n => format!("{n}{tag}"),
+        };
+        // Lines must not exceed 70 characters.
+        if line.len() + run.len() > 70 {
+            out += &line;
+            out.push('\n');
+            line.clear();
+        }
+        line += &run;
+    }
+    out += &line;
+    out.push('\n');
+    out
+}
+
+/// Draws the live cells of `pattern` white on a transparent image.
+fn pattern_image(pattern: &Pattern) -> (Image, UVec2) {
+    let size = pattern.size();
+    let mut image = Image::new_fill(
+        Extent3d {
+            width: size.x,
+            height: size.y,
+            depth_or_array_layers: 1,
+        },
+        TextureDimension::D2,
+        &[0, 0, 0, 0],
+        TextureFormat::Rgba8UnormSrgb,
+        RenderAssetUsages::RENDER_WORLD | RenderAssetUsages::MAIN_WORLD,
+    );
+    image.sampler = ImageSampler::nearest();
+    for cell in &pattern.cells {
+        if let Some(pixel) = image.pixel_bytes_mut(cell.extend(0)) {
+            pixel.copy_from_slice(&[255, 255, 255, 255]);
+        }
+    }
+    (image, size)
+}
+
+/// Loads `.rle` and `.cells` files as [`Stamp`]s.
+/// Unless the file says otherwise, the stamp costs one unit of seed money per
+/// live cell, is placed directly and is anchored at its middle. Patterns are
+/// never upscaled, as that would break them.
+#[derive(Default, TypePath)]
+pub struct StampLoader;
+impl AssetLoader for StampLoader {
+    type Asset = Stamp;
+    type Settings = ();
+    type Error = StampLoaderError;
+
+    async fn load(
+        &self,
+        reader: &mut dyn Reader,
+        _settings: &(),
+        load_context: &mut LoadContext<'_>,
+    ) -> Result<Stamp, Self::Error> {
+        let mut bytes = vec![];
+        reader.read_to_end(&mut bytes).await?;
+        let text = String::from_utf8(bytes)?;
+        let path = load_context.path().to_owned();
+        let pattern = match path.extension().and_then(|ext| ext.to_str()) {
+            Some("rle") => parse_rle(&text)?,
+            _ => parse_cells(&text)?,
+        };
+        if pattern.cells.is_empty() {
+            return Err(StampLoaderError::Empty);
+        }
+
+        let (image, size) = pattern_image(&pattern);
+        let texture = load_context.add_labeled_asset("image".into(), image);
+        let layout = TextureAtlasLayout::from_grid(size, 1, 1, None, None);
+        let layout = load_context.add_labeled_asset("layout".into(), layout);
+        let name = pattern.name.clone().unwrap_or_else(|| {
+            path.file_stem()
+                .map(|stem| stem.to_string_lossy().into_owned())
+                .unwrap_or_default()
+        });
+        Ok(Stamp {
+            atlas: TextureAtlas { layout, index: 0 },
+            texture,
+            name,
+            size,
+            anchor: size / 2,
+            upscale: false,
+            wake: pattern.wake,
+            cost: pattern.cost.unwrap_or(pattern.cells.len() as u32),
+            // Stamps from the editor have no catalog entry to group them.
+            category: if is_library_path(load_context.asset_path()) {
+                "My stamps".to_owned()
+            } else {
+                default()
+            },
+            description: default(),
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["rle", "cells"]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn written_rle_reads_back() {
+        let cells = vec![
+            UVec2::new(3, 2),
+            UVec2::new(4, 3),
+            UVec2::new(2, 4),
+            UVec2::new(3, 4),
+            UVec2::new(4, 4),
+        ];
+        let mut wake = WakeMask::default();
+        wake.toggle(1);
+        wake.toggle(7);
+        let text = write_rle("Glider", &cells, 25, Some(wake));
+
+        let pattern = parse_rle(&text).unwrap();
+        assert_eq!(pattern.name.as_deref(), Some("Glider"));
+        assert_eq!(pattern.cost, Some(25));
+        assert_eq!(pattern.wake, Some(wake));
+        // Written trimmed to the bounding box.
+        let expected = cells.iter().map(|cell| cell - UVec2::new(2, 2));
+        assert_eq!(pattern.cells, expected.collect::<Vec<_>>());
+    }
+
+    fn cells(pattern: &Pattern) -> Vec<(u32, u32)> {
+        pattern.cells.iter().map(|cell| (cell.x, cell.y)).collect()
+    }
+
+    #[test]
+    fn rle_runs_repeat_cells_and_rows() {
+        let pattern = parse_rle("x = 4, y = 4\n3o$b2o2$4o!").unwrap();
+        assert_eq!(
+            cells(&pattern),
+            [
+                (0, 0),
+                (1, 0),
+                (2, 0),
+                (1, 1),
+                (2, 1),
+                (0, 3),
+                (1, 3),
+                (2, 3),
+                (3, 3)
+            ]
+        );
+    }
+
+    #[test]
+    fn rle_stops_at_bang() {
+        let pattern = parse_rle("x = 2, y = 1\n2o!\nthis is not a pattern").unwrap();
+        assert_eq!(cells(&pattern), [(0, 0), (1, 0)]);
+    }
+
+    #[test]
+    fn rle_rows_span_lines() {
+        let pattern = parse_rle("x = 3, y = 2, rule = B3/S23\nob\no$2o\no!").unwrap();
+        assert_eq!(cells(&pattern), [(0, 0), (2, 0), (0, 1), (1, 1), (2, 1)]);
+    }
+
+    #[test]
+    fn rle_ignores_comments_that_dont_parse() {
+        let pattern = parse_rle("#C cost: a lot\n#C wake: 1,x\n#C made by hand\nx = 1, y = 1\no!");
+        let pattern = pattern.unwrap();
+        assert_eq!(pattern.cost, None);
+        assert_eq!(pattern.wake, None);
+        assert_eq!(cells(&pattern), [(0, 0)]);
+    }
+
+    #[test]
+    fn cells_reads_o_as_alive_and_dots_as_dead() {
+        let pattern = parse_cells("!Name: Glider\n!\n.O\n..O\nOOO\n").unwrap();
+        assert_eq!(pattern.name.as_deref(), Some("Glider"));
+        assert_eq!(cells(&pattern), [(1, 0), (2, 1), (0, 2), (1, 2), (2, 2)]);
+    }
+}
@@ -0,0 +1,213 @@
+//! The stamp catalog. Every stamp offered in the game is listed in
+//! `assets/stamps/catalog.stamps.ron`, so adding one only means editing
+//! assets. The catalog is an asset like any other and reloads when the file
+//! changes, in builds with Bevy's `file_watcher` feature. `dev_native` turns
+//! it on; release builds don't watch their assets.
+
+use bevy::{
+    asset::{AssetLoader, LoadContext, io::Reader},
+    prelude::*,
+};
+use serde::Deserialize;
+use thiserror::Error;
+use tiny_bail::prelude::*;
+
+use super::{Stamp, Stamps};
+
+pub const MANIFEST_PATH: &str = "stamps/catalog.stamps.ron";
+
+#[derive(Asset, TypePath, Debug, Clone, Deserialize)]
+pub struct StampManifest {
+    pub sheets: Vec<SpriteSheet>,
+    pub stamps: Vec<StampEntry>,
+}
+
+/// A sprite sheet cut into a grid of equally sized tiles.
+#[derive(Debug, Clone, Deserialize)]
+pub struct SpriteSheet {
+    pub path: String,
+    /// Width and height of a tile in pixels.
+    pub tile: (u32, u32),
+    pub columns: u32,
+    pub rows: u32,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub enum StampSource {
+    /// The tile at atlas `index` of the sprite sheet at index `sheet`.
+    Sheet { sheet: usize, index: usize },
+    /// A pattern file, see [`super::StampLoader`].
+    Pattern(String),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct StampEntry {
+    /// Key into [`Stamps`], also shown to the players.
+    pub name: String,
+    pub source: StampSource,
+    pub cost: u32,
+    #[serde(default)]
+    pub category: String,
+    #[serde(default)]
+    pub description: String,
+    /// The cell placed under the cursor. Defaults to the middle.
+    #[serde(default)]
+    pub anchor: Option<(u32, u32)>,
+    /// Whether the stamp grows with the board, see [`Stamp::upscale_for`].
+    /// Defaults to on for sprites and off for patterns, which stop working
+    /// when scaled.
+    #[serde(default)]
+    pub upscale: Option<bool>,
+}
+impl StampEntry {
+    /// Copies the catalog data onto a loaded stamp.
+    /// Returns false if the stamp already matches.
+    pub fn describe(&self, stamp: &mut Stamp) -> bool {
+        let before = (
+            stamp.cost,
+            stamp.category.clone(),
+            stamp.description.clone(),
+            stamp.anchor,
+            stamp.upscale,
+        );
+        stamp.cost = self.cost;
+        stamp.category.clone_from(&self.category);
+        stamp.description.clone_from(&self.description);
+        if let Some((x, y)) = self.anchor {
+            stamp.anchor = UVec2::new(x, y).min(stamp.size.saturating_sub(UVec2::ONE));
+        }
+        if let Some(upscale) = self.upscale {
+            stamp.upscale = upscale;
+        }
+        before
+            != (
+                stamp.cost,
+                stamp.category.clone(),
+                stamp.description.clone(),
+                stamp.anchor,
+                stamp.upscale,
+            )
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum StampManifestError {
+    #[error("could not read stamp manifest: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("invalid stamp manifest: {0}")]
+    Ron(#[from] ron::error::SpannedError),
+}
+
+#[derive(Default, TypePath)]
+pub struct StampManifestLoader;
+impl AssetLoader for StampManifestLoader {
+    type Asset = StampManifest;
+    type Settings = ();
+    type Error = StampManifestError;
+
+    async fn load(
+        &self,
+        reader: &mut dyn Reader,
+        _settings: &(),
+        _load_context: &mut LoadContext<'_>,
+    ) -> Result<StampManifest, Self::Error> {
+        let mut bytes = vec![];
+        reader.read_to_end(&mut bytes).await?;
+        Ok(ron::de::from_bytes(&bytes)?)
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["stamps.ron"]
+    }
+}
+
+/// Rebuilds [`Stamps`] whenever the catalog is loaded or edited.
+pub(super) fn apply_manifest(
+    mut events: EventReader<AssetEvent<StampManifest>>,
+    manifests: Res<Assets<StampManifest>>,
+    assets: Res<AssetServer>,
+    mut layouts: ResMut<Assets<TextureAtlasLayout>>,
+    mut stamps: ResMut<Stamps>,
+    mut stamp_assets: ResMut<Assets<Stamp>>,
+) {
+    let id = stamps.manifest.id();
+    let reloaded = events.read().any(|event| {
+        matches!(
+            event,
+            AssetEvent::LoadedWithDependencies { id: loaded } | AssetEvent::Modified { id: loaded }
+                if *loaded == id
+        )
+    });
+    if !reloaded {
+        return;
+    }
+    let manifest = r!(manifests.get(id));
+    stamps.catalog.clear();
+    stamps.patterns.clear();
+
+    let sheets = manifest
+        .sheets
+        .iter()
+        .map(|sheet| {
+            let tile = UVec2::new(sheet.tile.0, sheet.tile.1);
+            let layout = TextureAtlasLayout::from_grid(tile, sheet.columns, sheet.rows, None, None);
+            (assets.load(&sheet.path), layouts.add(layout), tile)
+        })
+        .collect::<Vec<(Handle<Image>, _, _)>>();
+    for entry in &manifest.stamps {
+        let handle = match &entry.source {
+            StampSource::Sheet { sheet, index } => {
+                let (texture, layout, size) = c!(sheets.get(*sheet));
+                let mut stamp = Stamp {
+                    texture: texture.clone(),
+                    atlas: TextureAtlas {
+                        layout: layout.clone(),
+                        index: *index,
+                    },
+                    name: entry.name.clone(),
+                    size: *size,
+                    anchor: *size / 2,
+                    upscale: true,
+                    wake: None,
+                    cost: entry.cost,
+                    category: default(),
+                    description: default(),
+                };
+                entry.describe(&mut stamp);
+                stamp_assets.add(stamp)
+            }
+            StampSource::Pattern(path) => {
+                let handle: Handle<Stamp> = assets.load(path);
+                if let Some(stamp) = stamp_assets.get_mut(&handle) {
+                    entry.describe(stamp);
+                }
+                stamps.patterns.insert(handle.id(), entry.clone());
+                handle
+            }
+        };
+        stamps.catalog.insert(entry.name.clone(), handle);
+    }
+    stamps.add_library(&stamp_assets);
+}
+
+/// Pattern files know nothing about the catalog, so its data is copied onto
+/// them once they are loaded.
+pub(super) fn describe_patterns(
+    mut events: EventReader<AssetEvent<Stamp>>,
+    stamps: Res<Stamps>,
+    mut stamp_assets: ResMut<Assets<Stamp>>,
+) {
+    for event in events.read() {
+        let (AssetEvent::LoadedWithDependencies { id } | AssetEvent::Modified { id }) = event
+        else {
+            continue;
+        };
+        let entry = c!(stamps.patterns.get(id));
+        // Only write when something differs, as writing sends another event.
+        let stamp = c!(stamp_assets.get(*id));
+        let mut described = stamp.clone();
+        if entry.describe(&mut described) {
+            *c!(stamp_assets.get_mut(*id)) = described;
+        }
+    }
+}
@@ -1,13 +1,109 @@
 use anyhow::anyhow;
-use bevy::{image::TextureAccessError, platform::collections::HashMap, prelude::*};
+use bevy::{ecs::system::SystemParam, platform::collections::HashMap, prelude::*};
 use itertools::Itertools;
 
+use crate::sim::{Cell, CellGrid, DormantSeed, TeamID, WakeMask};
+
+mod library;
+mod loader;
+mod manifest;
+pub use library::*;
+pub use loader::*;
+pub use manifest::*;
+
+/// Orientation and size of a placed stamp. Mirroring happens before
+/// rotating, so the eight combinations cover every orientation.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Reflect)]
+pub struct StampTransform {
+    /// Clockwise quarter turns.
+    pub rotation: u8,
+    /// Mirrored left to right.
+    pub flip_x: bool,
+    /// Mirrored top to bottom.
+    pub flip_y: bool,
+    /// Every pattern cell becomes a square of this many cells.
+    pub scale: u8,
+}
+impl Default for StampTransform {
+    fn default() -> Self {
+        Self {
+            rotation: 0,
+            flip_x: false,
+            flip_y: false,
+            scale: 1,
+        }
+    }
+}
+impl StampTransform {
+    pub const MAX_SCALE: u8 = 4;
+
+    pub fn rotated(self) -> Self {
+        Self {
+            rotation: (self.rotation + 1) % 4,
+            ..self
+        }
+    }
+    pub fn rotated_back(self) -> Self {
+        Self {
+            rotation: (self.rotation + 3) % 4,
+            ..self
+        }
+    }
+    pub fn flipped_x(self) -> Self {
+        Self {
+            flip_x: !self.flip_x,
+            ..self
+        }
+    }
+    pub fn flipped_y(self) -> Self {
+        Self {
+            flip_y: !self.flip_y,
+            ..self
+        }
+    }
+    /// Grows or shrinks by `delta`, staying within `1..=MAX_SCALE`.
+    pub fn scaled(self, delta: i8) -> Self {
+        Self {
+            scale: self
+                .scale
+                .saturating_add_signed(delta)
+                .clamp(1, Self::MAX_SCALE),
+            ..self
+        }
+    }
+    /// The cells covered by the pattern cell at `offset`.
+    pub fn apply(&self, offset: IVec2) -> impl Iterator<Item = IVec2> {
+        let flip = IVec2::new(
+            if self.flip_x { -1 } else { 1 },
+            if self.flip_y { -1 } else { 1 },
+        );
+        let turned =
+            (0..self.rotation % 4).fold(offset * flip, |offset, _| IVec2::new(-offset.y, offset.x));
+        let scale = self.scale.max(1) as i32;
+        (0..scale)
+            .flat_map(move |y| (0..scale).map(move |x| IVec2::new(x, y)))
+            .map(move |cell| turned * scale + cell)
+    }
+}
+
 #[derive(Clone, Debug, Asset, Reflect)]
 pub struct Stamp {
     pub atlas: TextureAtlas,
     pub texture: Handle<Image>,
     pub name: String,
-    pub size: u32,
+    /// Width and height in cells, before any scaling.
+    pub size: UVec2,
+    /// The cell placed under the cursor, counted from the top left.
+    pub anchor: UVec2,
+    /// Grows with the board, see [`Stamp::upscale_for`].
+    pub upscale: bool,
+    /// Stamps with a wake condition are planted as dormant seeds.
+    pub wake: Option<WakeMask>,
+    /// Seed money spent on placing the stamp in a match.
+    pub cost: u32,
+    /// Groups stamps in the selector.
+    pub category: String,
+    pub description: String,
 }
 impl Stamp {
     pub fn get_pixel_data(
@@ -30,116 +126,315 @@ impl Stamp {
             .collect_vec();
         Ok(res)
     }
-    pub fn add_to_texture<'a>(
-        &'a self,
-        texture: &'a mut Image,
+    /// Offsets of the opaque pixels from the anchor of the stamp.
+    pub fn pattern(
+        &self,
+        images: &Assets<Image>,
+        atlases: &Assets<TextureAtlasLayout>,
+    ) -> anyhow::Result<Vec<IVec2>> {
+        let mid = self.anchor.as_ivec2();
+        let data = self.get_pixel_data(images, atlases)?;
+        let res = data
+            .iter()
+            .enumerate()
+            .flat_map(|(x, column)| {
+                column
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, color)| color[3] != 0)
+                    .map(move |(y, _)| IVec2::new(x as i32, y as i32) - mid)
+            })
+            .collect_vec();
+        Ok(res)
+    }
+    /// How many cells across each pixel covers on a board of `board` cells.
+    /// Stamps are drawn for boards of up to [`UPSCALE_BOARD`] times their size
+    /// and grow with larger boards, unless `upscale` is off.
+    pub fn upscale_for(&self, board: UVec2) -> u8 {
+        if !self.upscale {
+            return 1;
+        }
+        let drawn_for = self.size.max_element().max(1) * UPSCALE_BOARD;
+        board
+            .max_element()
+            .div_ceil(drawn_for)
+            .clamp(1, u8::MAX as u32) as u8
+    }
+    /// `transform` with the board's upscale applied on top.
+    fn on_board(&self, transform: StampTransform, board: UVec2) -> StampTransform {
+        StampTransform {
+            scale: transform
+                .scale
+                .max(1)
+                .saturating_mul(self.upscale_for(board)),
+            ..transform
+        }
+    }
+    /// Activates every opaque pixel of the stamp for `owner`, anchored on
+    /// `pos`. Pixels outside the grid are dropped.
+    pub fn add_to_grid(
+        &self,
+        grid: &mut CellGrid,
         pos: Vec2,
+        transform: StampTransform,
+        owner: TeamID,
         images: &Assets<Image>,
         atlases: &Assets<TextureAtlasLayout>,
-    ) -> anyhow::Result<&'a mut Image> {
-        let mid = self.size as f32 / 2.;
-        let range = |pos| (pos - mid) as u32..(pos + mid) as u32;
-        let data = &self.get_pixel_data(images, atlases)?;
-        for (stamp_x, sim_x) in range(pos.x).enumerate() {
-            for (stamp_y, sim_y) in range(pos.y).enumerate() {
-                let color = &data[stamp_x][stamp_y];
-                if color[3] != 0 {
-                    match texture.set_color_at(
-                        sim_x,
-                        sim_y,
-                        Color::srgb_u8(color[0], color[1], color[2]),
-                    ) {
-                        Err(TextureAccessError::OutOfBounds { x: _, y: _, z: _ }) | Ok(_) => {}
-                        Err(e) => {
-                            error!("{e:#?}");
-                        }
-                    }
+    ) -> anyhow::Result<()> {
+        let center = pos.floor().as_ivec2();
+        let transform = self.on_board(transform, grid.size);
+        for offset in self.pattern(images, atlases)? {
+            for cell in transform.apply(offset).map(|offset| center + offset) {
+                if cell.cmpge(IVec2::ZERO).all() {
+                    grid.set(cell.as_uvec2(), Cell::ACTIVE.with_owner(Some(owner)));
                 }
             }
         }
-        Ok(texture)
+        Ok(())
+    }
+    /// Plants the stamp as a single dormant seed at `pos`, which expands into
+    /// the stamp once `mask` is met.
+    pub fn plant_in_grid(
+        &self,
+        grid: &mut CellGrid,
+        pos: Vec2,
+        transform: StampTransform,
+        owner: TeamID,
+        mask: WakeMask,
+        images: &Assets<Image>,
+        atlases: &Assets<TextureAtlasLayout>,
+    ) -> anyhow::Result<()> {
+        let transform = self.on_board(transform, grid.size);
+        let seed = DormantSeed {
+            pattern: self
+                .pattern(images, atlases)?
+                .into_iter()
+                .flat_map(|offset| transform.apply(offset))
+                .collect(),
+            mask,
+            owner,
+            awake: false,
+        };
+        grid.plant(pos.floor().as_uvec2(), seed)
+            .ok_or(anyhow!("could not plant seed"))?;
+        Ok(())
     }
 }
 
+/// Boards up to this many times a stamp's size across show it at its drawn
+/// size, e.g. 32 cells for an 8 pixel stamp.
+pub const UPSCALE_BOARD: u32 = 4;
+
+/// Every stamp on offer, whatever the board size.
 #[derive(Resource, Clone, Debug, Default)]
 pub struct Stamps {
-    pub px8: HashMap<String, Handle<Stamp>>,
-    pub px16: HashMap<String, Handle<Stamp>>,
-    pub px32: HashMap<String, Handle<Stamp>>,
+    pub catalog: HashMap<String, Handle<Stamp>>,
+    /// Keeps the catalog loaded so edits to it are picked up.
+    pub manifest: Handle<StampManifest>,
+    /// Catalog entries of the loaded pattern files, see [`describe_patterns`].
+    pub patterns: HashMap<AssetId<Stamp>, StampEntry>,
+    /// Stamps from the user's library, see [`save_to_library`].
+    pub library: Vec<Handle<Stamp>>,
 }
-#[allow(unused)]
 impl Stamps {
-    /// Square.
-    pub fn stamp_size_from_sim_size(size: u32) -> u32 {
-        match size {
-            0..=32 => 8,
-            33..=64 => 16,
-            _ => 32,
-        }
+    /// The stamp named `name`.
+    pub fn get_stamp<'a>(&self, name: &str, stamp_assets: &'a Assets<Stamp>) -> Option<&'a Stamp> {
+        self.catalog
+            .get(name)
+            .and_then(|handle| stamp_assets.get(handle))
     }
-    pub fn get_from_stamp_size(&self, size: u32) -> &HashMap<String, Handle<Stamp>> {
-        if size == 32 {
-            &self.px32
-        } else if size == 16 {
-            &self.px16
-        } else {
-            &self.px8
+    /// Whether `name` is taken by a stamp which isn't from the library.
+    pub fn is_catalog_stamp(&self, name: &str) -> bool {
+        self.catalog
+            .get(name)
+            .is_some_and(|handle| !self.library.contains(handle))
+    }
+    /// Offers every loaded library stamp under the name in its file, unless
+    /// the catalog already has a stamp by that name.
+    pub fn add_library(&mut self, stamp_assets: &Assets<Stamp>) {
+        for handle in &self.library {
+            let Some(stamp) = stamp_assets.get(handle) else {
+                continue;
+            };
+            if self.is_catalog_stamp(&stamp.name) {
+                warn!("Library stamp '{}' clashes with the catalog", stamp.name);
+                continue;
+            }
+            self.catalog.insert(stamp.name.clone(), handle.clone());
         }
     }
-    pub fn get_from_stamp_size_mut(&mut self, size: u32) -> &mut HashMap<String, Handle<Stamp>> {
-        if size == 32 {
-            &mut self.px32
-        } else if size == 16 {
-            &mut self.px16
-        } else {
-            &mut self.px8
+}
+
+/// A stamp on the board which has not been committed yet.
+#[derive(Debug, Clone, PartialEq)]
+pub struct StagedStamp {
+    /// Key into [`Stamps`].
+    pub name: String,
+    /// Center of the stamp in grid coordinates.
+    pub position: Vec2,
+    pub transform: StampTransform,
+    /// Planted as a dormant seed with this mask instead of placed directly.
+    pub wake: Option<WakeMask>,
+}
+
+/// Placements waiting for confirmation. The last one is selected, which is
+/// the one that moves, rotates and gets removed. Every change can be undone
+/// until the placements are taken out with [`StagedStamps::take`].
+#[derive(Resource, Debug, Clone, Default)]
+pub struct StagedStamps {
+    pub stamps: Vec<StagedStamp>,
+    history: Vec<Vec<StagedStamp>>,
+    /// Given to the next stamp staged, as shown under the cursor.
+    pub pending: StampTransform,
+}
+impl StagedStamps {
+    pub fn is_empty(&self) -> bool {
+        self.stamps.is_empty()
+    }
+    pub fn selected(&self) -> Option<&StagedStamp> {
+        self.stamps.last()
+    }
+    /// Remembers the current placements, then edits them.
+    fn edit(&mut self, f: impl FnOnce(&mut Vec<StagedStamp>) -> bool) -> bool {
+        let before = self.stamps.clone();
+        let changed = f(&mut self.stamps);
+        if changed {
+            self.history.push(before);
         }
+        changed
+    }
+    pub fn stage(&mut self, stamp: StagedStamp) {
+        self.edit(|stamps| {
+            stamps.push(stamp);
+            true
+        });
+    }
+    pub fn move_selected(&mut self, position: Vec2) -> bool {
+        self.edit(|stamps| {
+            stamps
+                .last_mut()
+                .filter(|stamp| stamp.position != position)
+                .map(|stamp| stamp.position = position)
+                .is_some()
+        })
     }
-    pub fn get_from_sim_size(&self, size: u32) -> &HashMap<String, Handle<Stamp>> {
-        let size = Self::stamp_size_from_sim_size(size);
-        self.get_from_stamp_size(size)
+    pub fn rotate_selected(&mut self) -> bool {
+        self.transform_selected(StampTransform::rotated)
     }
-    pub fn get_from_sim_size_mut(&mut self, size: u32) -> &mut HashMap<String, Handle<Stamp>> {
-        let size = Self::stamp_size_from_sim_size(size);
-        self.get_from_stamp_size_mut(size)
+    pub fn transform_selected(&mut self, f: impl FnOnce(StampTransform) -> StampTransform) -> bool {
+        self.edit(|stamps| {
+            stamps
+                .last_mut()
+                .and_then(|stamp| {
+                    let transform = f(stamp.transform);
+                    (transform != stamp.transform).then(|| stamp.transform = transform)
+                })
+                .is_some()
+        })
+    }
+    pub fn remove_selected(&mut self) -> bool {
+        self.edit(|stamps| stamps.pop().is_some())
+    }
+    pub fn undo(&mut self) -> bool {
+        self.history
+            .pop()
+            .map(|stamps| self.stamps = stamps)
+            .is_some()
+    }
+    /// Drops every placement and the undo history.
+    pub fn clear(&mut self) {
+        self.stamps.clear();
+        self.history.clear();
+        self.pending = default();
+    }
+    /// Hands over the placements for committing, see [`StampAssets::place`].
+    pub fn take(&mut self) -> Vec<StagedStamp> {
+        self.history.clear();
+        std::mem::take(&mut self.stamps)
+    }
+}
+
+/// Everything needed to look up stamps and draw them onto a grid.
+#[derive(SystemParam)]
+pub struct StampAssets<'w> {
+    pub stamps: Res<'w, Stamps>,
+    pub stamp_assets: Res<'w, Assets<Stamp>>,
+    pub images: Res<'w, Assets<Image>>,
+    pub atlases: Res<'w, Assets<TextureAtlasLayout>>,
+}
+impl StampAssets<'_> {
+    /// The stamp named `name`.
+    pub fn get(&self, name: &str) -> Option<&Stamp> {
+        self.stamps.get_stamp(name, &self.stamp_assets)
+    }
+    /// Stages `name` at `position`. It is planted as a seed if `wake_mask` is
+    /// set, or if the stamp comes with a wake condition of its own.
+    pub fn stage(
+        &self,
+        name: &str,
+        position: Vec2,
+        transform: StampTransform,
+        wake_mask: WakeMask,
+    ) -> Option<StagedStamp> {
+        let stamp = self.get(name)?;
+        Some(StagedStamp {
+            name: name.to_owned(),
+            position,
+            transform,
+            wake: Some(wake_mask)
+                .filter(|mask| !mask.is_empty())
+                .or(stamp.wake),
+        })
+    }
+    /// Draws a staged stamp onto `grid`, scaled up to suit the board.
+    /// Seeds are planted for `owner`.
+    pub fn place(
+        &self,
+        staged: &StagedStamp,
+        grid: &mut CellGrid,
+        owner: TeamID,
+    ) -> anyhow::Result<()> {
+        let stamp = self.get(&staged.name).ok_or(anyhow!("stamp"))?;
+        let (images, atlases) = (&self.images, &self.atlases);
+        match staged.wake {
+            Some(mask) => stamp.plant_in_grid(
+                grid,
+                staged.position,
+                staged.transform,
+                owner,
+                mask,
+                images,
+                atlases,
+            ),
+            None => stamp.add_to_grid(
+                grid,
+                staged.position,
+                staged.transform,
+                owner,
+                images,
+                atlases,
+            ),
+        }
     }
 }
 
 pub struct StampPlugin;
 impl Plugin for StampPlugin {
     fn build(&self, app: &mut App) {
-        app.add_systems(Startup, init)
+        app.add_systems(Startup, (init, load_library))
+            .add_systems(
+                Update,
+                (apply_manifest, describe_patterns, add_library_stamps).chain(),
+            )
             .init_asset::<Stamp>()
-            .init_resource::<Stamps>();
+            .init_asset::<StampManifest>()
+            .init_asset_loader::<StampLoader>()
+            .init_asset_loader::<StampManifestLoader>()
+            .init_resource::<Stamps>()
+            .init_resource::<StagedStamps>();
     }
 }
 
-fn init(
-    assets: Res<AssetServer>,
-    mut layouts: ResMut<Assets<TextureAtlasLayout>>,
-    mut stamps: ResMut<Stamps>,
-    mut stamp_assets: ResMut<Assets<Stamp>>,
-) {
-    for size in [8, 16, 32] {
-        let texture = assets.load(format!("sprites/stamps/{size}px.png"));
-        let layout = TextureAtlasLayout::from_grid(UVec2::splat(size), 5, 1, None, None);
-        let layout = layouts.add(layout.clone());
-        let stamps = stamps.get_from_stamp_size_mut(size);
-        for (i, name) in ["Square", "Noise", "Star", "Diag 1", "Diag 2"]
-            .iter()
-            .enumerate()
-        {
-            let stamp = Stamp {
-                texture: texture.clone(),
-                atlas: TextureAtlas {
-                    layout: layout.clone(),
-                    index: i,
-                },
-                name: format!("{name} ({size}px)"),
-                size,
-            };
-            let handle = stamp_assets.add(stamp);
-            stamps.insert(name.to_string(), handle);
-        }
-    }
+fn init(assets: Res<AssetServer>, mut stamps: ResMut<Stamps>) {
+    stamps.manifest = assets.load(MANIFEST_PATH);
 }
@@ -14,7 +14,24 @@ pub enum CurrentScreen {
     Init,
     MainMenu,
     GameSettings,
+    SeedSelection,
     MainLoop,
     Results,
     Sandbox,
+    StampEditor,
+}
+
+/// Turn phases of the main loop.
+#[derive(SubStates, Copy, Clone, Default, Hash, PartialEq, Eq, Debug)]
+#[source(CurrentScreen = CurrentScreen::MainLoop)]
+pub enum TurnState {
+    /// Waiting for the board to be set up.
+    #[default]
+    Loading,
+    /// The current player picks a stamp and clicks the board.
+    Placing,
+    /// The placement is shown and waits to be confirmed or cancelled.
+    Confirming,
+    /// The board runs for `steps_per_turn` steps, longer if seeds wake.
+    Simulating,
 }
@@ -4,13 +4,21 @@ use strum::IntoEnumIterator;
 use crate::ui::{
     data::{CurrentScreen, ScreenRoot},
     screens::{
-        init::InitScreenPlugin, main_menu::MainMenuScreenPlugin, sandbox::SandboxScreenPlugin,
+        game_settings::GameSettingsScreenPlugin, init::InitScreenPlugin,
+        main_loop::MainLoopScreenPlugin, main_menu::MainMenuScreenPlugin,
+        results::ResultsScreenPlugin, sandbox::SandboxScreenPlugin,
+        seed_selection::SeedSelectionScreenPlugin, stamp_editor::StampEditorScreenPlugin,
     },
 };
 
+pub mod game_settings;
 pub mod init;
+pub mod main_loop;
 pub mod main_menu;
+pub mod results;
 pub mod sandbox;
+pub mod seed_selection;
+pub mod stamp_editor;
 
 pub struct ScreensPlugin;
 impl Plugin for ScreensPlugin {
@@ -19,7 +27,12 @@ impl Plugin for ScreensPlugin {
             app.init_state::<CurrentScreen>()
                 .add_plugins(InitScreenPlugin)
                 .add_plugins(MainMenuScreenPlugin)
+                .add_plugins(GameSettingsScreenPlugin)
+                .add_plugins(SeedSelectionScreenPlugin)
+                .add_plugins(MainLoopScreenPlugin)
+                .add_plugins(ResultsScreenPlugin)
                 .add_plugins(SandboxScreenPlugin)
+                .add_plugins(StampEditorScreenPlugin)
         };
         for screen in CurrentScreen::iter() {
             app.add_systems(OnExit(screen), cleanup_screen);
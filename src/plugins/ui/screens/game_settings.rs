@@ -0,0 +1,343 @@
+use bevy::prelude::*;
+use bevy_hui::prelude::*;
+use tiny_bail::prelude::*;
+
+use crate::{
+    sim::{Player, SimSettings, Team, TurnOrder, VictoryCondition},
+    ui::{
+        Slider,
+        screens::{CurrentScreen, ScreenRoot},
+        widgets::data::{
+            SelectInput, SelectionChangedEvent, SliderChangedEvent, TextInputChangedEvent,
+        },
+    },
+};
+
+pub const MAX_TEAMS: usize = 4;
+pub const MAX_PLAYERS_PER_TEAM: usize = 2;
+
+#[derive(Default, Debug, strum::Display, Copy, Clone, PartialEq, Eq)]
+pub enum TeamColor {
+    #[default]
+    Red,
+    Blue,
+    Green,
+    Yellow,
+    Purple,
+    Orange,
+}
+impl TeamColor {
+    pub fn rgba(&self) -> [u8; 4] {
+        match self {
+            Self::Red => [255, 0, 0, 255],
+            Self::Blue => [0, 0, 255, 255],
+            Self::Green => [0, 200, 0, 255],
+            Self::Yellow => [255, 220, 0, 255],
+            Self::Purple => [160, 0, 255, 255],
+            Self::Orange => [255, 128, 0, 255],
+        }
+    }
+}
+impl TryFrom<&String> for TeamColor {
+    type Error = anyhow::Error;
+    fn try_from(value: &String) -> anyhow::Result<Self> {
+        match value.as_str() {
+            "Red" => Ok(Self::Red),
+            "Blue" => Ok(Self::Blue),
+            "Green" => Ok(Self::Green),
+            "Yellow" => Ok(Self::Yellow),
+            "Purple" => Ok(Self::Purple),
+            "Orange" => Ok(Self::Orange),
+            _ => Err(anyhow::anyhow!("No such color")),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct TeamSetup {
+    pub name: String,
+    pub color: TeamColor,
+    pub players: [String; MAX_PLAYERS_PER_TEAM],
+}
+
+/// Every team slot on the screen. Only the first `team_count` slots, and the
+/// first `players_per_team` players of each, end up in the match.
+/// Defaults match the template.
+#[derive(Resource, Clone, Debug, PartialEq)]
+pub struct MatchSetup {
+    pub team_count: usize,
+    pub players_per_team: usize,
+    pub teams: [TeamSetup; MAX_TEAMS],
+}
+impl Default for MatchSetup {
+    fn default() -> Self {
+        let colors = [
+            TeamColor::Red,
+            TeamColor::Blue,
+            TeamColor::Green,
+            TeamColor::Yellow,
+        ];
+        Self {
+            team_count: 2,
+            players_per_team: 1,
+            teams: std::array::from_fn(|i| TeamSetup {
+                name: colors[i].to_string(),
+                color: colors[i],
+                players: std::array::from_fn(|j| format!("Player {}", j * MAX_TEAMS + i + 1)),
+            }),
+        }
+    }
+}
+impl MatchSetup {
+    /// Fills in the teams and players of `settings`.
+    pub fn apply(&self, settings: &mut SimSettings) -> anyhow::Result<()> {
+        let teams = &self.teams[..self.team_count];
+        for (i, team) in teams.iter().enumerate() {
+            if team.name.trim().is_empty() {
+                anyhow::bail!("Team {} needs a name", i + 1);
+            }
+            if team.players[..self.players_per_team]
+                .iter()
+                .any(|name| name.trim().is_empty())
+            {
+                anyhow::bail!("Every player on {} needs a name", team.name);
+            }
+            if teams[..i].iter().any(|other| other.color == team.color) {
+                anyhow::bail!("{} has the same color as another team", team.name);
+            }
+        }
+        settings.teams = teams
+            .iter()
+            .enumerate()
+            .map(|(id, team)| Team {
+                id,
+                name: team.name.trim().to_owned(),
+                players: vec![],
+                color: team.color.rgba(),
+            })
+            .collect();
+        settings.players.clear();
+        // Interleave the teams so turns alternate between them, see `commit_state`.
+        for slot in 0..self.players_per_team {
+            for (id, team) in teams.iter().enumerate() {
+                settings.teams[id].players.push(settings.players.len());
+                settings.players.push(Player {
+                    team: id,
+                    name: team.players[slot].trim().to_owned(),
+                });
+            }
+        }
+        Ok(())
+    }
+}
+
+pub struct GameSettingsScreenPlugin;
+impl Plugin for GameSettingsScreenPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<MatchSetup>()
+            .add_observer(on_slider_input_change)
+            .add_observer(on_select_change)
+            .add_observer(on_text_input_change)
+            .add_systems(Startup, register)
+            .add_systems(OnEnter(CurrentScreen::GameSettings), render)
+            .add_systems(
+                Update,
+                show_team_slots.run_if(in_state(CurrentScreen::GameSettings)),
+            );
+    }
+}
+
+fn render(
+    mut commands: Commands,
+    server: Res<AssetServer>,
+    mut setup: ResMut<MatchSetup>,
+    mut settings: ResMut<SimSettings>,
+) {
+    // Every match starts from the defaults shown in the template.
+    *setup = MatchSetup::default();
+    *settings = SimSettings::default();
+    commands.spawn((
+        ScreenRoot,
+        HtmlNode(server.load("hui/screens/game_settings.xml")),
+    ));
+}
+
+fn register(
+    mut html_comps: HtmlComponents,
+    mut html_funcs: HtmlFunctions,
+    server: Res<AssetServer>,
+) {
+    html_comps.register(
+        "game_settings_team",
+        server.load("hui/screens/game_settings/team.xml"),
+    );
+    html_funcs.register(
+        "start_match",
+        |In(_),
+         setup: Res<MatchSetup>,
+         mut settings: ResMut<SimSettings>,
+         mut texts: Query<(&Tags, &mut Text)>,
+         mut screen: ResMut<NextState<CurrentScreen>>| {
+            let message = match setup.apply(&mut settings) {
+                Ok(()) => {
+                    info!("Starting match with {} teams", settings.teams.len());
+                    screen.set(CurrentScreen::SeedSelection);
+                    String::new()
+                }
+                Err(e) => e.to_string(),
+            };
+            let mut error_text = r!(texts
+                .iter_mut()
+                .find(|(tags, _)| tags.get("name").is_some_and(|n| n == "setup_error"))
+                .ok_or("text 'setup_error' not found"));
+            error_text.1.0 = message;
+        },
+    );
+}
+
+/// Hides the team and player slots which are not part of the match.
+fn show_team_slots(setup: Res<MatchSetup>, mut slots: Query<(&Tags, &mut HtmlStyle)>) {
+    for (tags, mut style) in &mut slots {
+        let visible = if let Some(team) = tags.get("team") {
+            c!(team.parse::<usize>()) < setup.team_count
+        } else if tags.get("second_player").is_some() {
+            setup.players_per_team > 1
+        } else {
+            continue;
+        };
+        style.computed.node.display = if visible {
+            Display::Flex
+        } else {
+            Display::None
+        };
+    }
+}
+
+fn on_select_change(
+    trigger: Trigger<SelectionChangedEvent>,
+    screen: Res<State<CurrentScreen>>,
+    selects: Query<&SelectInput>,
+    tags: Query<&Tags>,
+    mut setup: ResMut<MatchSetup>,
+    mut settings: ResMut<SimSettings>,
+) {
+    if **screen != CurrentScreen::GameSettings {
+        return;
+    }
+    let event = trigger.event();
+    let select = r!(selects.get(event.select));
+    let tags = r!(tags.get(event.select));
+    let name = r!(tags.get("name").ok_or("tag 'name' not found"));
+    match name.as_str() {
+        "team_count_select" => {
+            setup.team_count = r!(select.value.parse::<usize>()).clamp(2, MAX_TEAMS);
+        }
+        "players_per_team_select" => {
+            setup.players_per_team =
+                r!(select.value.parse::<usize>()).clamp(1, MAX_PLAYERS_PER_TEAM);
+        }
+        "turn_order_select" => {
+            settings.turn_order = r!(TurnOrder::try_from(&select.value));
+        }
+        "victory_select" => {
+            settings.victory = r!(VictoryCondition::try_from(&select.value));
+        }
+        name => {
+            let team = r!(name
+                .strip_prefix("team_color_")
+                .and_then(|team| team.parse::<usize>().ok())
+                .filter(|team| *team < MAX_TEAMS)
+                .ok_or("Unknown select"));
+            setup.teams[team].color = r!(TeamColor::try_from(&select.value));
+        }
+    }
+}
+
+fn on_slider_input_change(
+    trigger: Trigger<SliderChangedEvent>,
+    screen: Res<State<CurrentScreen>>,
+    sliders: Query<(&Slider, &UiTarget, &Tags)>,
+    mut settings: ResMut<SimSettings>,
+    mut texts: Query<&mut Text>,
+) {
+    if **screen != CurrentScreen::GameSettings {
+        return;
+    }
+    let (slider, target, tags) = r!(sliders.get(trigger.slider));
+    let mut text = r!(texts.get_mut(target.0));
+    let name = r!(tags.get("name").ok_or("tag 'name' not found"));
+    match name.as_str() {
+        "game_size_slider" => {
+            let value = u32::pow(2, (5. + slider.value * 4.).round() as u32);
+            settings.size = value;
+            text.0 = value.to_string();
+        }
+        "seed_money_slider" => {
+            let value = ((slider.value * 50.) as u32) * 10;
+            settings.seed_money = value;
+            text.0 = value.to_string();
+        }
+        "seeds_per_player_slider" => {
+            let value = (slider.value * 9.).round() as u32 + 1;
+            settings.seeds_per_player = value;
+            text.0 = value.to_string();
+        }
+        "game_steps_slider" => {
+            let value = ((slider.value * 99.) as u32) * 10 + 10;
+            settings.steps_per_turn = value;
+            text.0 = value.to_string();
+        }
+        "rounds_slider" => {
+            let value = (slider.value * 9.).round() as u32 + 1;
+            settings.rounds = value;
+            text.0 = value.to_string();
+        }
+        "victory_share_slider" => {
+            let value = (slider.value * 8.).round() as u32 * 10 + 10;
+            settings.victory_share = value as f32 / 100.;
+            text.0 = value.to_string();
+        }
+        "max_steps_slider" => {
+            let value = ((slider.value * 99.) as u32) * 10 + 10;
+            settings.max_steps_per_turn = value;
+            text.0 = value.to_string();
+        }
+        _ => {
+            warn!("Unknown name {name}")
+        }
+    }
+}
+
+fn on_text_input_change(
+    trigger: Trigger<TextInputChangedEvent>,
+    screen: Res<State<CurrentScreen>>,
+    tags: Query<&Tags>,
+    mut setup: ResMut<MatchSetup>,
+) {
+    if **screen != CurrentScreen::GameSettings {
+        return;
+    }
+    let event = trigger.event();
+    let tags = r!(tags.get(event.input));
+    let name = r!(tags.get("name").ok_or("tag 'name' not found"));
+    if let Some(team) = name.strip_prefix("team_name_") {
+        let team = r!(team
+            .parse::<usize>()
+            .ok()
+            .filter(|team| *team < MAX_TEAMS)
+            .ok_or("bad team index"));
+        setup.teams[team].name = event.value.clone();
+    } else if let Some(slot) = name.strip_prefix("player_name_") {
+        let (team, player) = r!(slot
+            .split_once('_')
+            .and_then(|(team, player)| Some((
+                team.parse::<usize>().ok()?,
+                player.parse::<usize>().ok()?
+            )))
+            .filter(|(team, player)| *team < MAX_TEAMS && *player < MAX_PLAYERS_PER_TEAM)
+            .ok_or("bad player index"));
+        setup.teams[team].players[player] = event.value.clone();
+    } else {
+        warn!("Unknown text input: {name}")
+    }
+}
@@ -0,0 +1,406 @@
+use bevy::prelude::*;
+use bevy_hui::prelude::*;
+use tiny_bail::prelude::*;
+
+use crate::{
+    sim::{
+        CellGrid, MatchStatus, SimGameplayState, SimSettings, SimState, StampEvent, TeamID,
+        TurnOrder, VictoryCondition, check_victory,
+    },
+    stamps::{StagedStamp, StagedStamps, StampAssets},
+    ui::{
+        data::TurnState,
+        screens::{CurrentScreen, ScreenRoot},
+    },
+};
+
+pub struct MainLoopScreenPlugin;
+impl Plugin for MainLoopScreenPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_sub_state::<TurnState>()
+            .init_resource::<HiddenPlacements>()
+            .init_resource::<RejectedPlacements>()
+            .add_observer(on_stamp)
+            .add_observer(submit_placements)
+            .add_systems(Startup, register)
+            .add_systems(OnEnter(CurrentScreen::MainLoop), render)
+            .add_systems(OnExit(CurrentScreen::MainLoop), close_sim)
+            .add_systems(
+                Update,
+                (
+                    start_sim.run_if(in_state(TurnState::Loading)),
+                    follow_staging.run_if(in_state(CurrentScreen::MainLoop)),
+                    update_turn_info.run_if(in_state(CurrentScreen::MainLoop)),
+                ),
+            )
+            .add_systems(
+                OnEnter(TurnState::Placing),
+                (unlock_preview, skip_idle_players),
+            )
+            .add_systems(OnEnter(TurnState::Confirming), lock_preview)
+            .add_systems(OnEnter(TurnState::Simulating), run_turn)
+            .add_systems(
+                OnTransition {
+                    exited: SimState::Running,
+                    entered: SimState::Paused,
+                },
+                finish_turn
+                    .after(check_victory)
+                    .run_if(in_state(TurnState::Simulating)),
+            );
+    }
+}
+
+fn render(
+    mut commands: Commands,
+    server: Res<AssetServer>,
+    mut settings: ResMut<SimSettings>,
+    mut gameplay: ResMut<SimGameplayState>,
+    mut staged: ResMut<StagedStamps>,
+    mut hidden: ResMut<HiddenPlacements>,
+    mut rejected: ResMut<RejectedPlacements>,
+) {
+    // Set again once the new sim image spawns.
+    settings.parent_node = None;
+    staged.clear();
+    hidden.clear();
+    rejected.0 = None;
+    let seeds = gameplay
+        .loadouts
+        .iter()
+        .map(|loadout| loadout.len() as u32)
+        .collect::<Vec<_>>();
+    gameplay.total_turns =
+        settings
+            .turn_order
+            .total_turns(&settings.players, &settings.teams, &seeds);
+    commands.spawn((
+        ScreenRoot,
+        HtmlNode(server.load("hui/screens/main_loop.xml")),
+    ));
+}
+
+fn register(mut html_funcs: HtmlFunctions) {
+    html_funcs.register(
+        "cancel_placement",
+        |In(_),
+         turn: Option<Res<State<TurnState>>>,
+         mut staged: ResMut<StagedStamps>,
+         mut next: ResMut<NextState<TurnState>>| {
+            if turn.is_some_and(|turn| **turn == TurnState::Confirming) {
+                staged.clear();
+                next.set(TurnState::Placing);
+            }
+        },
+    );
+    html_funcs.register(
+        "pass_turn",
+        |In(_),
+         turn: Option<Res<State<TurnState>>>,
+         mut staged: ResMut<StagedStamps>,
+         mut commands: Commands| {
+            if turn.is_some_and(|turn| **turn == TurnState::Placing) {
+                staged.clear();
+                commands.trigger(SubmitPlacements(vec![]));
+            }
+        },
+    );
+    html_funcs.register(
+        "confirm_placement",
+        |In(_),
+         turn: Option<Res<State<TurnState>>>,
+         staged: Res<StagedStamps>,
+         mut commands: Commands| {
+            if turn.is_some_and(|turn| **turn == TurnState::Confirming) {
+                commands.trigger(SubmitPlacements(staged.stamps.clone()));
+            }
+        },
+    );
+}
+
+/// Ends the current player's placement.
+#[derive(Event, Debug, Clone)]
+struct SubmitPlacements(Vec<StagedStamp>);
+
+/// Why the last submission was turned down. Cleared once the staged stamps
+/// change.
+#[derive(Resource, Debug, Clone, Default)]
+struct RejectedPlacements(Option<String>);
+
+/// Placements of a simultaneous round which the other players haven't seen yet.
+#[derive(Resource, Debug, Clone, Default, Deref, DerefMut)]
+struct HiddenPlacements(Vec<(TeamID, StagedStamp)>);
+
+/// Uses up the submitted stamps and commits them to the board, then runs the
+/// turn. In a simultaneous round the stamps stay hidden until every player has
+/// placed, and are then revealed and run together.
+/// If any stamp can't be placed nothing is, and the player stays on the turn.
+fn submit_placements(
+    trigger: Trigger<SubmitPlacements>,
+    settings: Res<SimSettings>,
+    assets: StampAssets,
+    mut staged: ResMut<StagedStamps>,
+    mut hidden: ResMut<HiddenPlacements>,
+    mut rejected: ResMut<RejectedPlacements>,
+    mut grid: ResMut<CellGrid>,
+    mut gameplay: ResMut<SimGameplayState>,
+    mut next: ResMut<NextState<TurnState>>,
+) {
+    let team = settings.players[gameplay.current_player].team;
+    let placements = &trigger.event().0;
+    // Hidden placements of this round are planted along with these.
+    let new_seeds = hidden
+        .iter()
+        .map(|(_, placement)| placement)
+        .chain(placements)
+        .filter(|placement| placement.wake.is_some())
+        .count();
+    if grid.seeds.len() + new_seeds > CellGrid::MAX_SEEDS {
+        rejected.0 = Some(format!(
+            "The board can't hold more than {} dormant seeds.",
+            CellGrid::MAX_SEEDS
+        ));
+        return;
+    }
+    // Charge a copy so a rejected submission costs nothing.
+    let mut charged = gameplay.clone();
+    for placement in placements {
+        let Some(stamp) = assets.get(&placement.name) else {
+            rejected.0 = Some(format!("{} no longer exists.", placement.name));
+            return;
+        };
+        let scale = placement.transform.scale;
+        if charged.use_stamp(&placement.name, stamp.cost, scale) {
+            continue;
+        }
+        rejected.0 = Some(if charged.can_place(&placement.name, stamp.cost, 1) {
+            format!("You can't afford {} at {scale}x.", placement.name)
+        } else {
+            format!("You can't place another {}.", placement.name)
+        });
+        return;
+    }
+    *gameplay = charged;
+    staged.take();
+    rejected.0 = None;
+    hidden.extend(placements.iter().map(|placement| (team, placement.clone())));
+    if settings.turn_order == TurnOrder::Simultaneous {
+        gameplay.turn += 1;
+        gameplay.current_player =
+            settings
+                .turn_order
+                .player(gameplay.turn, &settings.players, &settings.teams);
+        if gameplay.turn % settings.players.len() as u32 != 0 {
+            next.set(TurnState::Placing);
+            return;
+        }
+    }
+    for (team, placement) in hidden.drain(..) {
+        c!(assets.place(&placement, &mut grid, team));
+    }
+    next.set(TurnState::Simulating);
+}
+
+/// Sets up the board once the sim image is on screen.
+fn start_sim(
+    settings: Res<SimSettings>,
+    mut sim_state: ResMut<NextState<SimState>>,
+    mut turn: ResMut<NextState<TurnState>>,
+) {
+    if settings.parent_node.is_some() {
+        sim_state.set(SimState::Init);
+        turn.set(TurnState::Placing);
+    }
+}
+
+fn close_sim(mut sim_state: ResMut<NextState<SimState>>) {
+    sim_state.set(SimState::Closed);
+}
+
+/// Clicking the board stages the selected stamp, if the player may place it.
+/// Once a stamp is staged, clicking moves it instead.
+fn on_stamp(
+    trigger: Trigger<StampEvent>,
+    turn: Option<Res<State<TurnState>>>,
+    gameplay: Res<SimGameplayState>,
+    assets: StampAssets,
+    mut staged: ResMut<StagedStamps>,
+) {
+    let position = trigger.event().position;
+    match turn.map(|turn| **turn) {
+        Some(TurnState::Placing) => {
+            let name = r!(gameplay.current_stamp.as_ref().ok_or("no stamp selected"));
+            let stamp = r!(assets.get(name).ok_or("stamp not found"));
+            if !gameplay.can_place(name, stamp.cost, staged.pending.scale) {
+                return;
+            }
+            let transform = staged.pending;
+            if let Some(placement) = assets.stage(name, position, transform, gameplay.wake_mask) {
+                staged.stage(placement);
+            }
+        }
+        Some(TurnState::Confirming) => {
+            staged.move_selected(position);
+        }
+        _ => {}
+    }
+}
+
+/// Placing a stamp, removing it or undoing either switches the turn phase.
+fn follow_staging(
+    turn: Option<Res<State<TurnState>>>,
+    staged: Res<StagedStamps>,
+    mut rejected: ResMut<RejectedPlacements>,
+    mut next: ResMut<NextState<TurnState>>,
+) {
+    if staged.is_changed() && rejected.0.is_some() {
+        rejected.0 = None;
+    }
+    let turn = r!(turn.ok_or("no turn state"));
+    // Leave confirmed or cancelled placements alone.
+    if !matches!(*next, NextState::Unchanged) {
+        return;
+    }
+    match **turn {
+        TurnState::Placing if !staged.is_empty() => next.set(TurnState::Confirming),
+        TurnState::Confirming if staged.is_empty() => next.set(TurnState::Placing),
+        _ => {}
+    }
+}
+
+fn unlock_preview(mut gameplay: ResMut<SimGameplayState>) {
+    gameplay.preview_locked = false;
+}
+
+fn lock_preview(mut gameplay: ResMut<SimGameplayState>) {
+    gameplay.preview_locked = true;
+}
+
+/// Players of knocked out teams, and players who have placed all their seeds,
+/// are skipped without running the sim. In a simultaneous round they pass
+/// instead, as the round runs once everyone is in.
+fn skip_idle_players(
+    settings: Res<SimSettings>,
+    mut gameplay: ResMut<SimGameplayState>,
+    status: Res<MatchStatus>,
+    mut commands: Commands,
+    mut screen: ResMut<NextState<CurrentScreen>>,
+) {
+    loop {
+        let player = r!(settings.players.get(gameplay.current_player));
+        let out_of_seeds = gameplay
+            .loadouts
+            .get(gameplay.current_player)
+            .is_some_and(Vec::is_empty);
+        if !out_of_seeds && !status.eliminated.contains(&player.team) {
+            return;
+        }
+        if settings.turn_order == TurnOrder::Simultaneous {
+            commands.trigger(SubmitPlacements(vec![]));
+            return;
+        }
+        gameplay.turn += 1;
+        if gameplay.turn >= gameplay.total_turns {
+            screen.set(CurrentScreen::Results);
+            return;
+        }
+        gameplay.current_player =
+            settings
+                .turn_order
+                .player(gameplay.turn, &settings.players, &settings.teams);
+    }
+}
+
+fn run_turn(mut sim_state: ResMut<NextState<SimState>>) {
+    sim_state.set(SimState::Running);
+}
+
+/// Runs once the turn's steps are done, captured cells are committed and
+/// the victory condition is checked.
+fn finish_turn(
+    gameplay: Res<SimGameplayState>,
+    settings: Res<SimSettings>,
+    status: Res<MatchStatus>,
+    mut turn: ResMut<NextState<TurnState>>,
+    mut screen: ResMut<NextState<CurrentScreen>>,
+) {
+    if status.winners.is_some() || gameplay.turn >= gameplay.total_turns {
+        screen.set(CurrentScreen::Results);
+    } else {
+        turn.set(TurnState::Placing);
+    }
+}
+
+fn update_turn_info(
+    gameplay: Res<SimGameplayState>,
+    settings: Res<SimSettings>,
+    turn: Option<Res<State<TurnState>>>,
+    rejected: Res<RejectedPlacements>,
+    mut texts: Query<(&Tags, &mut Text, &mut TextColor)>,
+) {
+    let Some(player) = settings.players.get(gameplay.current_player) else {
+        return;
+    };
+    let Some(team) = settings.teams.get(player.team) else {
+        return;
+    };
+    let total_turns = gameplay.total_turns;
+    let phase = match turn.map(|turn| **turn) {
+        Some(TurnState::Placing) if settings.turn_order == TurnOrder::Simultaneous => {
+            "Pick a seed and click the board to place it, or pass. \
+             Placements stay hidden until everyone has placed."
+        }
+        Some(TurnState::Placing) => {
+            "Pick a seed from your loadout and click the board to place it, or pass."
+        }
+        Some(TurnState::Confirming) => {
+            "Click to move, R to rotate, Delete to remove, Ctrl+Z to undo. Confirm to run the turn."
+        }
+        Some(TurnState::Simulating) => "Simulating...",
+        Some(TurnState::Loading) | None => "Loading...",
+    };
+    let [r, g, b, a] = team.color;
+    for (tags, mut text, mut color) in &mut texts {
+        let value = match tags.get("name").map(String::as_str) {
+            Some("turn_count") => {
+                format!(
+                    "Turn {} / {total_turns}",
+                    (gameplay.turn + 1).min(total_turns)
+                )
+            }
+            Some("turn_player") => {
+                color.set_if_neq(TextColor(Color::srgba_u8(r, g, b, a)));
+                format!("{} ({})", player.name, team.name)
+            }
+            Some("turn_phase") => rejected.0.as_deref().unwrap_or(phase).to_owned(),
+            Some("turn_goal") => match settings.victory {
+                VictoryCondition::MostTerritory => {
+                    format!("Most territory after {} rounds", settings.rounds)
+                }
+                VictoryCondition::Threshold => format!(
+                    "First to {:.0}% of the board",
+                    settings.victory_share * 100.
+                ),
+                VictoryCondition::Elimination => "Last team with territory wins".to_owned(),
+                VictoryCondition::KingOfTheHill => {
+                    "Hold most of the marked zones for a full round".to_owned()
+                }
+            },
+            Some("turn_seeds") => match gameplay.loadouts.get(gameplay.current_player) {
+                Some(loadout) if !loadout.is_empty() => {
+                    // Left over from the draft, spent on scaling seeds up.
+                    let money = gameplay
+                        .balance()
+                        .map(|balance| format!(" ({balance} money left)"))
+                        .unwrap_or_default();
+                    format!("Seeds: {}{money}", loadout.join(", "))
+                }
+                _ => "No seeds left".to_owned(),
+            },
+            _ => continue,
+        };
+        if text.0 != value {
+            text.0 = value;
+        }
+    }
+}
@@ -38,4 +38,10 @@ fn register(
             state.set(CurrentScreen::Sandbox);
         },
     );
+    html_funcs.register(
+        "goto_stamp_editor",
+        |In(_entity), mut state: ResMut<NextState<CurrentScreen>>| {
+            state.set(CurrentScreen::StampEditor);
+        },
+    );
 }
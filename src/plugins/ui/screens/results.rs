@@ -0,0 +1,128 @@
+use bevy::prelude::*;
+use bevy_hui::prelude::*;
+use itertools::Itertools;
+
+use crate::{
+    sim::{MatchStatus, SimSeed, SimSettings, TeamScore, TerritoryStats, score, winners},
+    ui::screens::{CurrentScreen, ScreenRoot},
+};
+
+/// Final standings, best first.
+#[derive(Resource, Clone, Debug, Default, Deref)]
+pub struct MatchResults(pub Vec<TeamScore>);
+
+pub struct ResultsScreenPlugin;
+impl Plugin for ResultsScreenPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<MatchResults>()
+            .add_systems(Startup, register)
+            .add_systems(
+                OnEnter(CurrentScreen::Results),
+                (score_match, render).chain(),
+            )
+            .add_systems(
+                Update,
+                update_title.run_if(in_state(CurrentScreen::Results)),
+            );
+    }
+}
+
+fn score_match(
+    stats: Res<TerritoryStats>,
+    settings: Res<SimSettings>,
+    mut results: ResMut<MatchResults>,
+) {
+    results.0 = score(&stats.current, &settings.teams);
+    info!("Match results: {:?}", results.0);
+}
+
+fn render(mut commands: Commands, server: Res<AssetServer>) {
+    commands.spawn((ScreenRoot, HtmlNode(server.load("hui/screens/results.xml"))));
+}
+
+fn register(mut html_funcs: HtmlFunctions) {
+    html_funcs.register("init_results", init_results);
+    html_funcs.register(
+        "rematch",
+        |In(_), mut settings: ResMut<SimSettings>, mut screen: ResMut<NextState<CurrentScreen>>| {
+            // Same teams and settings on a fresh board, with a fresh draft.
+            settings.seed = SimSeed::random();
+            screen.set(CurrentScreen::SeedSelection);
+        },
+    );
+}
+
+/// One row per team: rank, name, cells held and share of the board.
+fn init_results(
+    In(entity): In<Entity>,
+    mut commands: Commands,
+    results: Res<MatchResults>,
+    settings: Res<SimSettings>,
+) {
+    let text_node = |text: String, width: f32| {
+        (
+            Node {
+                width: Val::Percent(width),
+                ..Default::default()
+            },
+            Text::new(text),
+            TextFont::from_font_size(16.),
+        )
+    };
+    for score in results.iter() {
+        let Some(team) = settings.teams.get(score.team) else {
+            continue;
+        };
+        let [r, g, b, a] = team.color;
+        let row = commands
+            .spawn((
+                Node {
+                    display: Display::Flex,
+                    padding: UiRect::all(Val::Px(5.)),
+                    border: UiRect::bottom(Val::Px(1.)),
+                    ..Default::default()
+                },
+                BorderColor(Color::srgba_u8(255, 255, 255, 51)),
+                children![
+                    text_node(format!("#{}", score.rank), 15.),
+                    (
+                        text_node(team.name.clone(), 45.),
+                        TextColor(Color::srgba_u8(r, g, b, a)),
+                    ),
+                    text_node(format!("{} cells", score.cells), 20.),
+                    text_node(format!("{:.1}%", score.share * 100.), 20.),
+                ],
+            ))
+            .id();
+        commands.entity(entity).add_child(row);
+    }
+}
+
+/// Names the winners of the victory condition, or the teams with the most
+/// territory if the match ran out of turns.
+fn update_title(
+    results: Res<MatchResults>,
+    status: Res<MatchStatus>,
+    settings: Res<SimSettings>,
+    mut texts: Query<(&Tags, &mut Text)>,
+) {
+    let teams = match &status.winners {
+        Some(teams) => teams.clone(),
+        None => winners(&results).map(|score| score.team).collect_vec(),
+    };
+    let names = teams
+        .iter()
+        .filter_map(|team| settings.teams.get(*team))
+        .map(|team| team.name.as_str())
+        .collect_vec();
+    let title = match names.as_slice() {
+        [] => "No winner".to_owned(),
+        [winner] => format!("{winner} wins!"),
+        [rest @ .., last] => format!("Tie between {} and {last}!", rest.join(", ")),
+    };
+    for (tags, mut text) in &mut texts {
+        if tags.get("name").is_some_and(|n| n == "results_title") && text.0 != title {
+            text.0 = title.clone();
+        }
+    }
+}
@@ -3,11 +3,18 @@ use bevy_hui::prelude::*;
 use tiny_bail::prelude::*;
 
 use crate::{
-    sim::{SimLayout, SimSettings, SimState},
+    sim::{
+        BoundaryMode, CellGrid, Neighborhood, SimGameplayState, SimLayout, SimSeed, SimSettings,
+        SimState, StampEvent, TransitionTable, WakeMask,
+    },
+    stamps::{StagedStamps, StampAssets},
     ui::{
         Slider,
         screens::{CurrentScreen, ScreenRoot},
-        widgets::data::{SelectInput, SelectionChangedEvent, SliderChangedEvent},
+        widgets::data::{
+            SelectInput, SelectionChangedEvent, SliderChangedEvent, TextInput,
+            TextInputChangedEvent,
+        },
     },
 };
 
@@ -15,13 +22,30 @@ pub struct SandboxScreenPlugin;
 impl Plugin for SandboxScreenPlugin {
     fn build(&self, app: &mut App) {
         app.add_observer(on_slider_input_change)
+            .add_observer(on_stamp)
             .add_observer(on_select_change)
+            .add_observer(on_text_input_change)
             .add_systems(Startup, register)
-            .add_systems(OnEnter(CurrentScreen::Sandbox), render);
+            .add_systems(OnEnter(CurrentScreen::Sandbox), render)
+            .add_systems(
+                Update,
+                sync_seed_input.run_if(in_state(CurrentScreen::Sandbox)),
+            );
     }
 }
 
-fn render(mut commands: Commands, server: Res<AssetServer>) {
+fn render(
+    mut commands: Commands,
+    server: Res<AssetServer>,
+    mut gameplay: ResMut<SimGameplayState>,
+    mut staged: ResMut<StagedStamps>,
+) {
+    // The wake condition grid starts out empty and stamps are free.
+    gameplay.wake_mask = WakeMask::default();
+    gameplay.in_match = false;
+    gameplay.wallets.clear();
+    gameplay.loadouts.clear();
+    staged.clear();
     commands.spawn((ScreenRoot, HtmlNode(server.load("hui/screens/sandbox.xml"))));
 }
 
@@ -61,6 +85,34 @@ fn register(
             settings.layout = layout;
         },
     );
+    html_funcs.register(
+        "toggle_wake_cell",
+        |In(entity),
+         cells: Query<(&Tags, &UiTarget)>,
+         mut texts: Query<&mut Text>,
+         mut gameplay: ResMut<SimGameplayState>| {
+            let (tags, target) = r!(cells.get(entity));
+            let cell = r!(tags
+                .get("cell")
+                .and_then(|cell| cell.parse::<usize>().ok())
+                .ok_or("tag 'cell' not found"));
+            gameplay.wake_mask.toggle(cell);
+            let mut text = r!(texts.get_mut(target.0));
+            text.0 = if gameplay.wake_mask.is_set(cell) {
+                "x"
+            } else {
+                " "
+            }
+            .into();
+        },
+    );
+    html_funcs.register(
+        "randomize_seed",
+        |In(_), mut settings: ResMut<SimSettings>| {
+            settings.seed = SimSeed::random();
+        },
+    );
+    html_funcs.register("run_staged", run_staged);
     html_funcs.register(
         "goto_main_menu",
         |In(_), mut screen: ResMut<NextState<CurrentScreen>>| {
@@ -69,12 +121,58 @@ fn register(
     )
 }
 
+/// In the sandbox every click stages another stamp. Nothing runs until
+/// the placements are committed with `run_staged`.
+fn on_stamp(
+    trigger: Trigger<StampEvent>,
+    screen: Res<State<CurrentScreen>>,
+    sim_state: Res<State<SimState>>,
+    gameplay: Res<SimGameplayState>,
+    assets: StampAssets,
+    mut staged: ResMut<StagedStamps>,
+) {
+    if **screen != CurrentScreen::Sandbox || **sim_state != SimState::Paused {
+        return;
+    }
+    let name = r!(gameplay.current_stamp.as_ref().ok_or("no stamp selected"));
+    let position = trigger.event().position;
+    let transform = staged.pending;
+    if let Some(placement) = assets.stage(name, position, transform, gameplay.wake_mask) {
+        staged.stage(placement);
+    }
+}
+
+/// Commits every staged stamp and runs the sim.
+fn run_staged(
+    In(_): In<Entity>,
+    sim_state: Res<State<SimState>>,
+    gameplay: Res<SimGameplayState>,
+    settings: Res<SimSettings>,
+    assets: StampAssets,
+    mut staged: ResMut<StagedStamps>,
+    mut grid: ResMut<CellGrid>,
+    mut next: ResMut<NextState<SimState>>,
+) {
+    if **sim_state != SimState::Paused {
+        return;
+    }
+    let team = settings.players[gameplay.current_player].team;
+    for placement in staged.take() {
+        c!(assets.place(&placement, &mut grid, team));
+    }
+    next.set(SimState::Running);
+}
+
 fn on_select_change(
     trigger: Trigger<SelectionChangedEvent>,
+    screen: Res<State<CurrentScreen>>,
     selects: Query<&SelectInput>,
     tags: Query<&Tags>,
     mut settings: ResMut<SimSettings>,
 ) {
+    if **screen != CurrentScreen::Sandbox {
+        return;
+    }
     info!("on-select-change");
     let event = trigger.event();
     let select = r!(selects.get(event.select));
@@ -86,6 +184,16 @@ fn on_select_change(
             settings.layout = layout;
             info!("settings.layout = {}", settings.layout);
         }
+        "boundary_select" => {
+            let boundary = r!(BoundaryMode::try_from(&select.value));
+            settings.boundary = boundary;
+            info!("settings.boundary = {}", settings.boundary);
+        }
+        "neighborhood_select" => {
+            let neighborhood = r!(Neighborhood::try_from(&select.value));
+            settings.neighborhood = neighborhood;
+            info!("settings.neighborhood = {}", settings.neighborhood);
+        }
         _ => {
             warn!("Unknown select: {name}")
         }
@@ -94,10 +202,14 @@ fn on_select_change(
 
 fn on_slider_input_change(
     trigger: Trigger<SliderChangedEvent>,
+    screen: Res<State<CurrentScreen>>,
     sliders: Query<(&Slider, &UiTarget, &Tags)>,
     mut settings: ResMut<SimSettings>,
     mut texts: Query<&mut Text>,
 ) {
+    if **screen != CurrentScreen::Sandbox {
+        return;
+    }
     let (slider, target, tags) = r!(sliders.get(trigger.slider));
     let mut text = r!(texts.get_mut(target.0));
     let name = r!(tags.get("name").ok_or("tag 'name' not found"));
@@ -117,8 +229,89 @@ fn on_slider_input_change(
             settings.steps_per_turn = value;
             text.0 = value.to_string();
         }
+        "sim_max_steps_slider" => {
+            let value = ((slider.value * 99.) as u32) * 10 + 10;
+            settings.max_steps_per_turn = value;
+            text.0 = value.to_string();
+        }
         _ => {
             warn!("Unknown name {name}")
         }
     }
 }
+
+fn on_text_input_change(
+    trigger: Trigger<TextInputChangedEvent>,
+    screen: Res<State<CurrentScreen>>,
+    tags: Query<&Tags>,
+    mut texts: Query<(&Tags, &mut Text)>,
+    mut settings: ResMut<SimSettings>,
+) {
+    if **screen != CurrentScreen::Sandbox {
+        return;
+    }
+    let event = trigger.event();
+    let tags = r!(tags.get(event.input));
+    let name = r!(tags.get("name").ok_or("tag 'name' not found"));
+    let (error_name, message) = match name.as_str() {
+        "seed_input" => {
+            let message = match event.value.trim().parse::<u32>() {
+                Ok(seed) => {
+                    settings.seed = SimSeed(seed);
+                    String::new()
+                }
+                Err(e) => format!("Seed must be a number up to {}: {e}", u32::MAX),
+            };
+            ("seed_error", message)
+        }
+        "rule_input" => {
+            let message = match event.value.parse::<TransitionTable>() {
+                Ok(rule) => {
+                    settings.rule = rule;
+                    info!("settings.rule = {}", event.value);
+                    String::new()
+                }
+                Err(e) => e.to_string(),
+            };
+            ("rule_error", message)
+        }
+        _ => {
+            warn!("Unknown text input: {name}");
+            return;
+        }
+    };
+    let mut error_text = r!(texts
+        .iter_mut()
+        .find(|(tags, _)| tags.get("name").is_some_and(|n| n == error_name))
+        .ok_or("error text not found"));
+    error_text.1.0 = message;
+}
+
+/// Shows the current seed unless the player is typing a new one.
+/// Replacing a seed that didn't parse also clears its error.
+fn sync_seed_input(
+    settings: Res<SimSettings>,
+    mut inputs: Query<(&Tags, &mut TextInput, &UiTarget)>,
+    mut texts: Query<(&mut Text, Option<&Tags>)>,
+) {
+    let seed = settings.seed.to_string();
+    for (tags, mut input, target) in &mut inputs {
+        if input.focused
+            || input.value == seed
+            || tags.get("name").is_none_or(|n| n != "seed_input")
+        {
+            continue;
+        }
+        input.value = seed.clone();
+        if let Ok((mut text, _)) = texts.get_mut(**target) {
+            text.0 = seed.clone();
+        }
+        for (mut text, tags) in &mut texts {
+            let is_error =
+                tags.is_some_and(|tags| tags.get("name").is_some_and(|n| n == "seed_error"));
+            if is_error && !text.0.is_empty() {
+                text.0.clear();
+            }
+        }
+    }
+}
@@ -0,0 +1,448 @@
+//! The draft before a match. Every player buys the seeds they get to place,
+//! while each candidate loops through a short preview of what it does.
+
+use std::{sync::Arc, time::Duration};
+
+use bevy::{
+    asset::RenderAssetUsages,
+    prelude::*,
+    render::render_resource::{Extent3d, TextureDimension, TextureFormat},
+    ui::RelativeCursorPosition,
+};
+use bevy_hui::prelude::*;
+use itertools::Itertools;
+use tiny_bail::prelude::*;
+
+use crate::{
+    sim::{
+        BLACK, Cell, CellGrid, CellKind, SimGameplayState, SimSettings, Team, WakeMask, step_cells,
+    },
+    stamps::{Stamp, Stamps},
+    ui::screens::{CurrentScreen, ScreenRoot},
+};
+
+/// Steps the finished preview stays on screen before it starts over.
+const PREVIEW_HOLD: u32 = 10;
+/// Displayed size of a preview board.
+pub(super) const PREVIEW_SIZE: f32 = 96.;
+
+pub struct SeedSelectionScreenPlugin;
+impl Plugin for SeedSelectionScreenPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<PreviewTimer>()
+            .add_systems(Startup, register)
+            .add_systems(OnEnter(CurrentScreen::SeedSelection), render)
+            .add_systems(
+                Update,
+                (update_cards, update_draft_info).run_if(in_state(CurrentScreen::SeedSelection)),
+            )
+            .add_systems(
+                Update,
+                run_previews.run_if(
+                    in_state(CurrentScreen::SeedSelection).or(in_state(CurrentScreen::StampEditor)),
+                ),
+            );
+    }
+}
+
+#[derive(Resource, Debug, Deref, DerefMut)]
+struct PreviewTimer(Timer);
+impl Default for PreviewTimer {
+    fn default() -> Self {
+        Self(Timer::from_seconds(0.1, TimerMode::Repeating))
+    }
+}
+
+#[derive(Component, Debug)]
+struct SeedCard {
+    name: String,
+    cost: u32,
+}
+
+/// A small board running one stamp on its own. Captured cells show green,
+/// enemy territory red.
+#[derive(Component, Debug)]
+pub(super) struct SeedPreview {
+    initial: CellGrid,
+    grid: CellGrid,
+    image: Handle<Image>,
+    step: u32,
+}
+impl SeedPreview {
+    /// Starts a preview of `initial`, drawn into a new image.
+    pub(super) fn new(initial: CellGrid, images: &mut Assets<Image>) -> Self {
+        let extent = Extent3d {
+            width: initial.size.x,
+            height: initial.size.y,
+            depth_or_array_layers: 1,
+        };
+        let image = images.add(Image::new_fill(
+            extent,
+            TextureDimension::D2,
+            BLACK,
+            TextureFormat::Rgba8UnormSrgb,
+            RenderAssetUsages::RENDER_WORLD | RenderAssetUsages::MAIN_WORLD,
+        ));
+        Self {
+            grid: initial.clone(),
+            initial,
+            image,
+            step: 0,
+        }
+    }
+    pub(super) fn image(&self) -> Handle<Image> {
+        self.image.clone()
+    }
+    pub(super) fn initial(&self) -> &CellGrid {
+        &self.initial
+    }
+    /// Starts over from `initial`.
+    pub(super) fn restart(&mut self, initial: CellGrid) {
+        self.grid.clone_from(&initial);
+        self.initial = initial;
+        self.step = 0;
+    }
+}
+
+fn render(
+    mut commands: Commands,
+    server: Res<AssetServer>,
+    settings: Res<SimSettings>,
+    mut gameplay: ResMut<SimGameplayState>,
+) {
+    // The draft hands out seeds one player at a time, see `finish_draft`.
+    let players = settings.players.len();
+    gameplay.current_player = 0;
+    gameplay.current_stamp = None;
+    gameplay.in_match = true;
+    gameplay.wallets = vec![settings.seed_money; players];
+    gameplay.loadouts = vec![vec![]; players];
+    commands.spawn((
+        ScreenRoot,
+        HtmlNode(server.load("hui/screens/seed_selection.xml")),
+    ));
+}
+
+fn register(mut html_funcs: HtmlFunctions) {
+    html_funcs.register("init_seed_cards", init_seed_cards);
+    html_funcs.register(
+        "undo_pick",
+        |In(_),
+         stamps: Res<Stamps>,
+         stamp_assets: Res<Assets<Stamp>>,
+         mut gameplay: ResMut<SimGameplayState>| {
+            let player = gameplay.current_player;
+            let name = r!(gameplay
+                .loadouts
+                .get_mut(player)
+                .and_then(|loadout| loadout.pop())
+                .ok_or("nothing to undo"));
+            let stamp = r!(stamps
+                .get_stamp(&name, &stamp_assets)
+                .ok_or("stamp not found"));
+            if let Some(balance) = gameplay.wallets.get_mut(player) {
+                *balance += stamp.cost;
+            }
+        },
+    );
+    html_funcs.register(
+        "finish_draft",
+        |In(_),
+         settings: Res<SimSettings>,
+         mut gameplay: ResMut<SimGameplayState>,
+         mut screen: ResMut<NextState<CurrentScreen>>| {
+            if gameplay.current_player + 1 < settings.players.len() {
+                gameplay.current_player += 1;
+            } else {
+                screen.set(CurrentScreen::MainLoop);
+            }
+        },
+    );
+}
+
+/// Board colors for the previews: the drafting team is green, the enemy red.
+fn preview_settings(settings: &SimSettings) -> SimSettings {
+    let team = |id, name: &str, color| Team {
+        id,
+        name: name.to_owned(),
+        players: vec![],
+        color,
+    };
+    SimSettings {
+        teams: vec![
+            team(0, "Own", [0, 200, 0, 255]),
+            team(1, "Enemy", [255, 0, 0, 255]),
+        ],
+        ..settings.clone()
+    }
+}
+
+/// The stamp in the middle, enemy territory along the bottom. Stamps with a
+/// wake condition are planted as a dormant seed, as they are in a match.
+fn preview_grid(
+    stamp: &Stamp,
+    images: &Assets<Image>,
+    atlases: &Assets<TextureAtlasLayout>,
+) -> anyhow::Result<CellGrid> {
+    let Some(mask) = stamp.wake else {
+        return Ok(preview_board(stamp.size, stamp.pattern(images, atlases)?));
+    };
+    let mut grid = preview_board(stamp.size, []);
+    let center = (grid.size / 2).as_vec2();
+    stamp.plant_in_grid(&mut grid, center, default(), 0, mask, images, atlases)?;
+    Ok(grid)
+}
+
+/// A square board three times the stamp's longer side across, with
+/// `pattern` in the middle, given as offsets from the stamp's anchor, and
+/// enemy territory along the bottom.
+pub(super) fn preview_board(
+    stamp_size: UVec2,
+    pattern: impl IntoIterator<Item = IVec2>,
+) -> CellGrid {
+    let stamp_size = stamp_size.max_element();
+    let size = stamp_size * 3;
+    let mut grid = CellGrid::new(UVec2::splat(size));
+    for y in stamp_size * 2..size {
+        for x in 0..size {
+            grid.set(UVec2::new(x, y), Cell::captured(1));
+        }
+    }
+    let center = IVec2::splat(size as i32 / 2);
+    for offset in pattern {
+        let cell = center + offset;
+        if cell.cmpge(IVec2::ZERO).all() {
+            grid.set(cell.as_uvec2(), Cell::ACTIVE);
+        }
+    }
+    grid
+}
+
+fn init_seed_cards(
+    In(entity): In<Entity>,
+    mut commands: Commands,
+    stamps: Res<Stamps>,
+    stamp_assets: Res<Assets<Stamp>>,
+    mut images: ResMut<Assets<Image>>,
+    atlases: Res<Assets<TextureAtlasLayout>>,
+    mut nodes: Query<&mut Node>,
+) {
+    let mut node = r!(nodes.get_mut(entity));
+    node.flex_wrap = FlexWrap::Wrap;
+    node.column_gap = Val::Px(8.);
+    node.row_gap = Val::Px(8.);
+    node.overflow = Overflow::scroll_y();
+
+    let candidates = stamps
+        .catalog
+        .iter()
+        .filter_map(|(name, handle)| Some((name.clone(), stamp_assets.get(handle)?)))
+        .sorted_by(|(a_name, a), (b_name, b)| a.cost.cmp(&b.cost).then(a_name.cmp(b_name)))
+        .collect_vec();
+    // #333
+    let border_color = Color::linear_rgb(3. / 16., 3. / 16., 3. / 16.);
+    let text_node = |text: String, size: f32| {
+        (
+            Pickable::IGNORE,
+            Text::new(text),
+            TextFont::from_font_size(size),
+        )
+    };
+    for (name, stamp) in candidates {
+        let initial = c!(preview_grid(stamp, &images, &atlases));
+        let preview = SeedPreview::new(initial, &mut images);
+        let mask = stamp.wake.unwrap_or_default();
+        let wake_grid = commands
+            .spawn((
+                Node {
+                    display: Display::Grid,
+                    grid_template_columns: vec![RepeatedGridTrack::px(3, 10.)],
+                    grid_template_rows: vec![RepeatedGridTrack::px(3, 10.)],
+                    column_gap: Val::Px(1.),
+                    row_gap: Val::Px(1.),
+                    ..Default::default()
+                },
+                Pickable::IGNORE,
+            ))
+            .with_children(|grid| {
+                for bit in 0..9 {
+                    let color = if bit == WakeMask::CENTER {
+                        Color::linear_rgb(0.5, 0.5, 0.5)
+                    } else if mask.is_set(bit) {
+                        Color::WHITE
+                    } else {
+                        Color::linear_rgb(0.1, 0.1, 0.1)
+                    };
+                    grid.spawn((Node::default(), BackgroundColor(color), Pickable::IGNORE));
+                }
+            })
+            .id();
+        let wake_text = if mask.is_empty() {
+            "Grows when placed"
+        } else {
+            "Dormant until woken"
+        };
+        let card = commands
+            .spawn((
+                Node {
+                    display: Display::Flex,
+                    flex_direction: FlexDirection::Column,
+                    align_items: AlignItems::Center,
+                    row_gap: Val::Px(4.),
+                    border: UiRect::all(Val::Px(2.)),
+                    padding: UiRect::all(Val::Px(5.)),
+                    ..Default::default()
+                },
+                BackgroundColor(Color::linear_rgb(0.1, 0.1, 0.1)),
+                BorderColor(border_color),
+                BorderRadius::all(Val::Px(5.)),
+                SeedCard {
+                    name: name.clone(),
+                    cost: stamp.cost,
+                },
+                Pickable {
+                    should_block_lower: true,
+                    is_hoverable: true,
+                },
+                RelativeCursorPosition::default(),
+                children![
+                    (
+                        Node {
+                            width: Val::Px(PREVIEW_SIZE),
+                            height: Val::Px(PREVIEW_SIZE),
+                            ..Default::default()
+                        },
+                        ImageNode::new(preview.image()),
+                        Pickable::IGNORE,
+                        preview,
+                    ),
+                    text_node(name, 16.),
+                    text_node(format!("Cost: {}", stamp.cost), 12.),
+                    (
+                        text_node(stamp.description.clone(), 10.),
+                        Node {
+                            max_width: Val::Px(PREVIEW_SIZE),
+                            ..Default::default()
+                        },
+                    ),
+                    text_node(wake_text.to_owned(), 10.),
+                ],
+            ))
+            .add_child(wake_grid)
+            .observe(draft_seed)
+            .id();
+        commands.entity(entity).add_child(card);
+    }
+}
+
+/// Adds the clicked seed to the drafting player's loadout.
+fn draft_seed(
+    event: Trigger<Pointer<Click>>,
+    cards: Query<&SeedCard>,
+    settings: Res<SimSettings>,
+    mut gameplay: ResMut<SimGameplayState>,
+) {
+    let card = r!(cards.get(event.target));
+    let player = gameplay.current_player;
+    let full = gameplay
+        .loadouts
+        .get(player)
+        .is_none_or(|loadout| loadout.len() >= settings.seeds_per_player as usize);
+    if full || !gameplay.spend(card.cost) {
+        return;
+    }
+    gameplay.loadouts[player].push(card.name.clone());
+}
+
+/// Steps every preview and starts it over once the turn is shown.
+fn run_previews(
+    time: Res<Time<Real>>,
+    mut timer: ResMut<PreviewTimer>,
+    settings: Res<SimSettings>,
+    mut previews: Query<&mut SeedPreview>,
+    mut images: ResMut<Assets<Image>>,
+) {
+    timer.set_duration(Duration::from_secs_f32(
+        1. / settings.timestep.max(1) as f32,
+    ));
+    if !timer.tick(time.delta()).just_finished() {
+        return;
+    }
+    let colors = preview_settings(&settings);
+    let rule = Arc::new(settings.rule);
+    for mut preview in &mut previews {
+        let preview = &mut *preview;
+        if preview.step < settings.steps_per_turn {
+            preview.grid.cells = step_cells(&preview.grid, 0, rule.clone(), &colors, preview.step);
+        } else if preview.step == settings.steps_per_turn {
+            // Same as `commit_state` at the end of a turn.
+            for cell in preview.grid.cells.iter_mut() {
+                if matches!(cell.kind, CellKind::Active | CellKind::Dying) {
+                    *cell = Cell::captured(0);
+                }
+            }
+        } else if preview.step >= settings.steps_per_turn + PREVIEW_HOLD {
+            preview.grid.clone_from(&preview.initial);
+            preview.step = 0;
+        }
+        preview.step += 1;
+        let image = c!(images.get_mut(&preview.image));
+        preview.grid.colorize(image, &colors, &[]);
+    }
+}
+
+/// Greys out seeds the drafting player can't buy.
+fn update_cards(
+    settings: Res<SimSettings>,
+    gameplay: Res<SimGameplayState>,
+    mut cards: Query<(&SeedCard, &mut BackgroundColor, &RelativeCursorPosition)>,
+) {
+    let picks = gameplay
+        .loadouts
+        .get(gameplay.current_player)
+        .map_or(0, Vec::len);
+    let full = picks >= settings.seeds_per_player as usize;
+    for (card, mut bg_color, cursor_pos) in &mut cards {
+        let color = if full || !gameplay.can_afford(card.cost) {
+            Color::linear_rgb(0.02, 0.02, 0.02)
+        } else if cursor_pos.mouse_over() {
+            Color::linear_rgb(0.2, 0.2, 0.2)
+        } else {
+            Color::linear_rgb(0.1, 0.1, 0.1)
+        };
+        bg_color.set_if_neq(BackgroundColor(color));
+    }
+}
+
+fn update_draft_info(
+    settings: Res<SimSettings>,
+    gameplay: Res<SimGameplayState>,
+    mut texts: Query<(&Tags, &mut Text, &mut TextColor)>,
+) {
+    let player = r!(settings.players.get(gameplay.current_player));
+    let team = r!(settings.teams.get(player.team));
+    let loadout = gameplay
+        .loadouts
+        .get(gameplay.current_player)
+        .cloned()
+        .unwrap_or_default();
+    let [r, g, b, a] = team.color;
+    for (tags, mut text, mut color) in &mut texts {
+        let value = match tags.get("name").map(String::as_str) {
+            Some("draft_player") => {
+                color.set_if_neq(TextColor(Color::srgba_u8(r, g, b, a)));
+                format!("{} ({})", player.name, team.name)
+            }
+            Some("draft_money") => format!("Seed money: {}", gameplay.balance().unwrap_or(0)),
+            Some("draft_count") => {
+                format!("Seeds: {} / {}", loadout.len(), settings.seeds_per_player)
+            }
+            Some("draft_loadout") if loadout.is_empty() => "Nothing drafted yet".to_owned(),
+            Some("draft_loadout") => loadout.join(", "),
+            _ => continue,
+        };
+        if text.0 != value {
+            text.0 = value;
+        }
+    }
+}
@@ -0,0 +1,295 @@
+//! Paints new stamps cell by cell. The pattern loops in a preview while it is
+//! drawn, and saving puts it in the user's stamp library, see
+//! [`save_to_library`].
+
+use bevy::prelude::*;
+use bevy_hui::prelude::*;
+use tiny_bail::prelude::*;
+
+use crate::{
+    sim::{SimGameplayState, WakeMask},
+    stamps::{Stamps, save_to_library},
+    ui::{
+        Slider,
+        screens::{
+            CurrentScreen, ScreenRoot,
+            seed_selection::{PREVIEW_SIZE, SeedPreview, preview_board},
+        },
+        widgets::data::{SliderChangedEvent, TextInputChangedEvent},
+    },
+};
+
+/// Cells across the canvas.
+const CANVAS_SIZE: u32 = 16;
+/// Displayed size of a canvas cell.
+const CELL_SIZE: f32 = 20.;
+const MIN_COST: u32 = 5;
+const MAX_COST: u32 = 100;
+
+pub struct StampEditorScreenPlugin;
+impl Plugin for StampEditorScreenPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<StampDraft>()
+            .add_observer(on_slider_input_change)
+            .add_observer(on_text_input_change)
+            .add_systems(Startup, register)
+            .add_systems(OnEnter(CurrentScreen::StampEditor), render)
+            .add_systems(
+                Update,
+                (update_canvas, update_preview)
+                    .run_if(in_state(CurrentScreen::StampEditor))
+                    .run_if(resource_changed::<StampDraft>),
+            );
+    }
+}
+
+/// The stamp being painted. The wake condition lives in
+/// [`SimGameplayState::wake_mask`] so the shared wake grid can edit it.
+#[derive(Resource, Debug, Clone)]
+struct StampDraft {
+    cells: Vec<bool>,
+    name: String,
+    cost: u32,
+    /// What dragging paints, set by the cell the drag started on.
+    paint: bool,
+}
+impl Default for StampDraft {
+    fn default() -> Self {
+        Self {
+            cells: vec![false; (CANVAS_SIZE * CANVAS_SIZE) as usize],
+            name: "New stamp".to_owned(),
+            cost: 20,
+            paint: true,
+        }
+    }
+}
+impl StampDraft {
+    fn index(cell: UVec2) -> usize {
+        (cell.y * CANVAS_SIZE + cell.x) as usize
+    }
+    fn live_cells(&self) -> Vec<UVec2> {
+        (0..CANVAS_SIZE)
+            .flat_map(|y| (0..CANVAS_SIZE).map(move |x| UVec2::new(x, y)))
+            .filter(|cell| self.cells[Self::index(*cell)])
+            .collect()
+    }
+    /// Live cells as offsets from the middle of the canvas.
+    fn pattern(&self) -> impl Iterator<Item = IVec2> {
+        let mid = IVec2::splat(CANVAS_SIZE as i32 / 2);
+        self.live_cells()
+            .into_iter()
+            .map(move |cell| cell.as_ivec2() - mid)
+    }
+}
+
+#[derive(Component, Debug, Copy, Clone)]
+struct CanvasCell(UVec2);
+
+#[derive(Component, Debug, Copy, Clone)]
+struct EditorPreview;
+
+fn render(
+    mut commands: Commands,
+    server: Res<AssetServer>,
+    mut draft: ResMut<StampDraft>,
+    mut gameplay: ResMut<SimGameplayState>,
+) {
+    *draft = default();
+    gameplay.wake_mask = WakeMask::default();
+    commands.spawn((
+        ScreenRoot,
+        HtmlNode(server.load("hui/screens/stamp_editor.xml")),
+    ));
+}
+
+fn register(mut html_funcs: HtmlFunctions) {
+    html_funcs.register("init_stamp_canvas", init_stamp_canvas);
+    html_funcs.register("init_stamp_preview", init_stamp_preview);
+    html_funcs.register("clear_canvas", |In(_), mut draft: ResMut<StampDraft>| {
+        draft.cells.fill(false);
+    });
+    html_funcs.register("save_stamp", save_stamp);
+}
+
+fn init_stamp_canvas(In(entity): In<Entity>, mut commands: Commands) {
+    let grid = commands
+        .spawn((
+            Node {
+                display: Display::Grid,
+                grid_template_columns: vec![RepeatedGridTrack::px(CANVAS_SIZE as u16, CELL_SIZE)],
+                grid_template_rows: vec![RepeatedGridTrack::px(CANVAS_SIZE as u16, CELL_SIZE)],
+                column_gap: Val::Px(1.),
+                row_gap: Val::Px(1.),
+                ..Default::default()
+            },
+            BackgroundColor(Color::linear_rgb(0.2, 0.2, 0.2)),
+        ))
+        .id();
+    for y in 0..CANVAS_SIZE {
+        for x in 0..CANVAS_SIZE {
+            let cell = commands
+                .spawn((
+                    Node::default(),
+                    BackgroundColor(Color::BLACK),
+                    CanvasCell(UVec2::new(x, y)),
+                ))
+                .observe(start_painting)
+                .observe(continue_painting)
+                .id();
+            commands.entity(grid).add_child(cell);
+        }
+    }
+    commands.entity(entity).add_child(grid);
+}
+
+/// Pressing a cell flips it, and the drag keeps painting that value.
+fn start_painting(
+    trigger: Trigger<Pointer<Pressed>>,
+    cells: Query<&CanvasCell>,
+    mut draft: ResMut<StampDraft>,
+) {
+    let cell = r!(cells.get(trigger.target()));
+    let index = StampDraft::index(cell.0);
+    draft.paint = !draft.cells[index];
+    draft.cells[index] = draft.paint;
+}
+
+fn continue_painting(
+    trigger: Trigger<Pointer<Over>>,
+    mouse: Res<ButtonInput<MouseButton>>,
+    cells: Query<&CanvasCell>,
+    mut draft: ResMut<StampDraft>,
+) {
+    if !mouse.pressed(MouseButton::Left) {
+        return;
+    }
+    let cell = r!(cells.get(trigger.target()));
+    let index = StampDraft::index(cell.0);
+    if draft.cells[index] != draft.paint {
+        draft.cells[index] = draft.paint;
+    }
+}
+
+fn update_canvas(draft: Res<StampDraft>, mut cells: Query<(&CanvasCell, &mut BackgroundColor)>) {
+    for (cell, mut color) in &mut cells {
+        let value = if draft.cells[StampDraft::index(cell.0)] {
+            Color::WHITE
+        } else {
+            Color::BLACK
+        };
+        color.set_if_neq(BackgroundColor(value));
+    }
+}
+
+fn init_stamp_preview(
+    In(entity): In<Entity>,
+    mut commands: Commands,
+    draft: Res<StampDraft>,
+    mut images: ResMut<Assets<Image>>,
+) {
+    let preview = SeedPreview::new(
+        preview_board(UVec2::splat(CANVAS_SIZE), draft.pattern()),
+        &mut images,
+    );
+    let node = commands
+        .spawn((
+            Node {
+                width: Val::Px(PREVIEW_SIZE * 2.),
+                height: Val::Px(PREVIEW_SIZE * 2.),
+                ..Default::default()
+            },
+            ImageNode::new(preview.image()),
+            preview,
+            EditorPreview,
+        ))
+        .id();
+    commands.entity(entity).add_child(node);
+}
+
+/// Restarts the preview whenever the pattern changes.
+fn update_preview(
+    draft: Res<StampDraft>,
+    mut previews: Query<&mut SeedPreview, With<EditorPreview>>,
+) {
+    let board = preview_board(UVec2::splat(CANVAS_SIZE), draft.pattern());
+    for mut preview in &mut previews {
+        if *preview.initial() != board {
+            preview.restart(board.clone());
+        }
+    }
+}
+
+fn save_stamp(
+    In(_): In<Entity>,
+    assets: Res<AssetServer>,
+    draft: Res<StampDraft>,
+    gameplay: Res<SimGameplayState>,
+    mut stamps: ResMut<Stamps>,
+    mut texts: Query<(&Tags, &mut Text)>,
+) {
+    let wake = Some(gameplay.wake_mask).filter(|mask| !mask.is_empty());
+    let message = match save_to_library(
+        &assets,
+        &mut stamps,
+        &draft.name,
+        &draft.live_cells(),
+        draft.cost,
+        wake,
+    ) {
+        Ok(()) => format!("Saved '{}' to your stamps.", draft.name.trim()),
+        Err(e) => format!("Could not save: {e}"),
+    };
+    for (tags, mut text) in &mut texts {
+        if tags.get("name").is_some_and(|n| n == "editor_status") {
+            text.0 = message.clone();
+        }
+    }
+}
+
+fn on_slider_input_change(
+    trigger: Trigger<SliderChangedEvent>,
+    screen: Res<State<CurrentScreen>>,
+    sliders: Query<(&Slider, &UiTarget, &Tags)>,
+    mut draft: ResMut<StampDraft>,
+    mut texts: Query<&mut Text>,
+) {
+    if **screen != CurrentScreen::StampEditor {
+        return;
+    }
+    let (slider, target, tags) = r!(sliders.get(trigger.slider));
+    let mut text = r!(texts.get_mut(target.0));
+    let name = r!(tags.get("name").ok_or("tag 'name' not found"));
+    match name.as_str() {
+        "stamp_cost_slider" => {
+            let steps = (MAX_COST - MIN_COST) / 5;
+            let value = MIN_COST + (slider.value * steps as f32).round() as u32 * 5;
+            draft.cost = value;
+            text.0 = value.to_string();
+        }
+        _ => {
+            warn!("Unknown name {name}")
+        }
+    }
+}
+
+fn on_text_input_change(
+    trigger: Trigger<TextInputChangedEvent>,
+    screen: Res<State<CurrentScreen>>,
+    tags: Query<&Tags>,
+    mut draft: ResMut<StampDraft>,
+) {
+    if **screen != CurrentScreen::StampEditor {
+        return;
+    }
+    let event = trigger.event();
+    let tags = r!(tags.get(event.input));
+    let name = r!(tags.get("name").ok_or("tag 'name' not found"));
+    match name.as_str() {
+        "stamp_name_input" => {
+            draft.name = event.value.clone();
+        }
+        _ => {
+            warn!("Unknown text input: {name}")
+        }
+    }
+}
@@ -12,6 +12,10 @@ pub mod sim_image;
 use sim_image::SimImageWidgetPlugin;
 pub mod scrollable;
 pub use scrollable::*;
+pub mod text_input;
+use text_input::TextInputWidgetPlugin;
+pub mod territory_stats;
+use territory_stats::TerritoryStatsWidgetPlugin;
 
 pub struct WidgetsPlugin;
 impl Plugin for WidgetsPlugin {
@@ -22,6 +26,8 @@ impl Plugin for WidgetsPlugin {
             StampSelectorWidgetPlugin,
             SimImageWidgetPlugin,
             ScrollableWidgetPlugin,
+            TextInputWidgetPlugin,
+            TerritoryStatsWidgetPlugin,
         ));
     }
 }
@@ -65,3 +65,18 @@ pub struct SliderNob {
 #[derive(Component, Default, Debug)]
 #[require(RelativeCursorPosition)]
 pub struct Scrollable;
+
+/// Text input holds the value being edited
+#[derive(Component, Default, Reflect, Debug)]
+#[reflect]
+pub struct TextInput {
+    pub value: String,
+    pub focused: bool,
+}
+
+#[derive(Event, Reflect, Debug, Clone)]
+#[reflect]
+pub struct TextInputChangedEvent {
+    pub input: Entity,
+    pub value: String,
+}
@@ -1,21 +1,35 @@
-use anyhow::anyhow;
-use bevy::{prelude::*, ui::RelativeCursorPosition};
-use bevy_hui::prelude::{HtmlComponents, HtmlFunctions};
+use bevy::{
+    input::mouse::{MouseScrollUnit, MouseWheel},
+    prelude::*,
+    ui::RelativeCursorPosition,
+};
+use bevy_hui::prelude::{HtmlComponents, HtmlFunctions, Tags};
+use tiny_bail::prelude::*;
 
 use crate::{
-    sim::{SimGameplayState, SimImages, SimSettings, SimState, StampEvent},
-    stamps::{Stamp, Stamps},
-    ui::data::TemplateHandles,
+    sim::{CellGrid, PreviewGrid, SimGameplayState, SimSettings, SimState, StampEvent},
+    stamps::{StagedStamps, StampAssets, StampTransform},
+    ui::{data::TemplateHandles, widgets::data::TextInput},
 };
 
 #[derive(Component, Debug, Copy, Clone)]
 pub struct SimImageNode;
 
+/// Pixel scrolling, as sent by trackpads, counts as one wheel notch per this
+/// many pixels.
+const SCROLL_PIXELS_PER_LINE: f32 = 50.;
+
 pub struct SimImageWidgetPlugin;
 impl Plugin for SimImageWidgetPlugin {
     fn build(&self, app: &mut App) {
-        app.add_systems(Startup, init)
-            .add_systems(Update, hover_preview);
+        app.add_systems(Startup, init).add_systems(
+            Update,
+            (
+                hover_preview,
+                edit_staged_keys.run_if(in_state(SimState::Paused)),
+                update_countdown,
+            ),
+        );
     }
 }
 
@@ -29,6 +43,33 @@ fn init(
     handles.insert("sim_image", handle.clone());
     components.register("sim_image", handle);
     funcs.register("init_sim_image", init_sim_image);
+    funcs.register(
+        "rotate_staged",
+        |In(_), mut staged: ResMut<StagedStamps>| {
+            staged.rotate_selected();
+        },
+    );
+    funcs.register(
+        "remove_staged",
+        |In(_), mut staged: ResMut<StagedStamps>| {
+            staged.remove_selected();
+        },
+    );
+    funcs.register(
+        "flip_staged_x",
+        |In(_), mut staged: ResMut<StagedStamps>| {
+            staged.transform_selected(StampTransform::flipped_x);
+        },
+    );
+    funcs.register(
+        "flip_staged_y",
+        |In(_), mut staged: ResMut<StagedStamps>| {
+            staged.transform_selected(StampTransform::flipped_y);
+        },
+    );
+    funcs.register("undo_staged", |In(_), mut staged: ResMut<StagedStamps>| {
+        staged.undo();
+    });
 }
 
 fn init_sim_image(
@@ -39,51 +80,185 @@ fn init_sim_image(
     settings.parent_node = Some(entity);
     commands
         .entity(entity)
-        .observe(|_: Trigger<Pointer<Click>>, mut commands: Commands| {
-            commands.trigger(StampEvent);
-        })
+        .observe(
+            |trigger: Trigger<Pointer<Click>>,
+             nodes: Query<&RelativeCursorPosition>,
+             grid: Res<CellGrid>,
+             mut commands: Commands| {
+                let cursor = r!(nodes.get(trigger.target()));
+                let pos = r!(cursor.normalized.ok_or("cursor not on the board"));
+                commands.trigger(StampEvent {
+                    position: pos * grid.size.as_vec2(),
+                });
+            },
+        )
         .insert((RelativeCursorPosition::default(), SimImageNode));
 }
 
+/// Where the selected stamp would be staged: under the cursor, unless the
+/// preview is locked.
+fn ghost_position(
+    cursor: &RelativeCursorPosition,
+    gameplay: &SimGameplayState,
+    grid: &CellGrid,
+) -> Option<Vec2> {
+    if gameplay.current_stamp.is_none() || gameplay.preview_locked {
+        return None;
+    }
+    let pos = cursor.normalized.filter(|_| cursor.mouse_over())?;
+    Some(pos * grid.size.as_vec2())
+}
+
+/// Shows the board with every staged stamp, plus the selected stamp under the
+/// cursor unless the preview is locked.
 fn hover_preview(
     pos: Single<&RelativeCursorPosition, With<SimImageNode>>,
     sim_state: Res<State<SimState>>,
     gameplay_state: Res<SimGameplayState>,
     settings: Res<SimSettings>,
-    sim_images: Res<SimImages>,
-    mut images: ResMut<Assets<Image>>,
-    stamps: Res<Stamps>,
-    stamp_assets: Res<Assets<Stamp>>,
-    atlases: Res<Assets<TextureAtlasLayout>>,
+    grid: Res<CellGrid>,
+    staged: Res<StagedStamps>,
+    mut preview: ResMut<PreviewGrid>,
+    assets: StampAssets,
 ) {
     if !matches!(**sim_state, SimState::Paused) {
         return;
     }
-    let Some(current_stamp) = gameplay_state.current_stamp.as_ref() else {
+    let mut stamps = staged.stamps.clone();
+    if let (Some(current_stamp), Some(pos)) = (
+        gameplay_state.current_stamp.as_ref(),
+        ghost_position(&pos, &gameplay_state, &grid),
+    ) {
+        stamps.extend(assets.stage(current_stamp, pos, staged.pending, gameplay_state.wake_mask));
+    }
+
+    let team = settings.players[gameplay_state.current_player].team;
+    let mut new_preview = grid.clone();
+    for stamp in &stamps {
+        if let Err(e) = assets.place(stamp, &mut new_preview, team) {
+            error!("Could not hover with error: {e}");
+        }
+    }
+    // Only touch the resource on change so the colorize pass stays idle.
+    if preview.0 != new_preview {
+        preview.0 = new_preview;
+    }
+}
+
+/// R rotates, F and V mirror left to right and top to bottom, and + and -
+/// scale. Over the board the mouse wheel rotates, or scales with Shift held.
+/// Scaled stamps cost more, see [`SimGameplayState::scaled_cost`].
+/// Scrolling adds up until it makes a full notch, so trackpads don't spin
+/// the stamp.
+/// These apply to the stamp under the cursor, or to the selected placement
+/// when there is none.
+/// Delete removes, the arrow keys nudge and Ctrl+Z undoes the selected
+/// placement.
+fn edit_staged_keys(
+    keys: Res<ButtonInput<KeyCode>>,
+    mut wheel: EventReader<MouseWheel>,
+    mut scrolled: Local<f32>,
+    cursor: Single<&RelativeCursorPosition, With<SimImageNode>>,
+    inputs: Query<&TextInput>,
+    gameplay: Res<SimGameplayState>,
+    grid: Res<CellGrid>,
+    mut staged: ResMut<StagedStamps>,
+) {
+    let scroll = wheel
+        .read()
+        .map(|event| match event.unit {
+            MouseScrollUnit::Line => event.x + event.y,
+            MouseScrollUnit::Pixel => (event.x + event.y) / SCROLL_PIXELS_PER_LINE,
+        })
+        .sum::<f32>();
+    if inputs.iter().any(|input| input.focused) {
         return;
-    };
-    let Some(pos) = pos.normalized else {
+    }
+    if keys.any_pressed([KeyCode::ControlLeft, KeyCode::ControlRight]) {
+        if keys.just_pressed(KeyCode::KeyZ) {
+            staged.undo();
+        }
         return;
-    };
-    if let Err(e) = (|| {
-        let stamps = stamps.get_from_sim_size(settings.size);
-        let stamp = stamps.get(current_stamp).ok_or(anyhow!("stamp"))?;
-        let stamp = stamp_assets.get(stamp).ok_or(anyhow!("stamp asset"))?;
-
-        let original = images
-            .get(&sim_images.texture_a)
-            .ok_or(anyhow!("texture_a"))?;
-        let mut new_preview = original.clone();
+    }
 
-        let pos = pos * Vec2::splat(settings.size as f32);
-        stamp.add_to_texture(&mut new_preview, pos, &images, &atlases)?;
+    let shift = keys.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight]);
+    *scrolled = if cursor.mouse_over() {
+        *scrolled + scroll
+    } else {
+        0.
+    };
+    let scroll = match *scrolled {
+        s if s >= 1. => 1,
+        s if s <= -1. => -1,
+        _ => 0,
+    };
+    if scroll != 0 {
+        *scrolled = 0.;
+    }
+    let (wheel_turns, wheel_scale) = if shift { (0, scroll) } else { (scroll, 0) };
+    let turns = keys.just_pressed(KeyCode::KeyR) as i8 - wheel_turns;
+    let scale = keys.any_just_pressed([KeyCode::Equal, KeyCode::NumpadAdd]) as i8
+        - keys.any_just_pressed([KeyCode::Minus, KeyCode::NumpadSubtract]) as i8
+        + wheel_scale;
+    let (flip_x, flip_y) = (
+        keys.just_pressed(KeyCode::KeyF),
+        keys.just_pressed(KeyCode::KeyV),
+    );
+    let edit = |mut transform: StampTransform| {
+        if flip_x {
+            transform = transform.flipped_x();
+        }
+        if flip_y {
+            transform = transform.flipped_y();
+        }
+        transform = match turns.signum() {
+            1 => transform.rotated(),
+            -1 => transform.rotated_back(),
+            _ => transform,
+        };
+        transform.scaled(scale)
+    };
+    if ghost_position(&cursor, &gameplay, &grid).is_some() {
+        let pending = edit(staged.pending);
+        if pending != staged.pending {
+            staged.pending = pending;
+        }
+    } else {
+        staged.transform_selected(edit);
+    }
+    if keys.any_just_pressed([KeyCode::Delete, KeyCode::Backspace]) {
+        staged.remove_selected();
+    }
+    let nudges = [
+        (KeyCode::ArrowLeft, Vec2::NEG_X),
+        (KeyCode::ArrowRight, Vec2::X),
+        (KeyCode::ArrowUp, Vec2::NEG_Y),
+        (KeyCode::ArrowDown, Vec2::Y),
+    ];
+    for (key, delta) in nudges {
+        if let Some(position) = staged
+            .selected()
+            .map(|stamp| stamp.position + delta)
+            .filter(|_| keys.just_pressed(key))
+        {
+            staged.move_selected(position);
+        }
+    }
+}
 
-        images
-            .get_mut(&sim_images.preview_texture)
-            .ok_or(anyhow!("preview_texture"))?
-            .clone_from(&new_preview);
-        anyhow::Ok(())
-    })() {
-        error!("Could not hover with error: {e}");
+/// Counts down the steps left in the running turn.
+fn update_countdown(
+    sim_state: Res<State<SimState>>,
+    gameplay: Res<SimGameplayState>,
+    mut texts: Query<(&Tags, &mut Text)>,
+) {
+    let value = match **sim_state {
+        SimState::Running => format!("{} steps left", gameplay.remaining_steps()),
+        _ => String::new(),
+    };
+    for (tags, mut text) in &mut texts {
+        if tags.get("name").is_some_and(|n| n == "steps_left") && text.0 != value {
+            text.0 = value.clone();
+        }
     }
 }
@@ -1,5 +1,6 @@
 use bevy::{prelude::*, ui::RelativeCursorPosition};
 use bevy_hui::prelude::*;
+use itertools::Itertools;
 
 use crate::{
     sim::SimGameplayState,
@@ -10,8 +11,15 @@ use crate::{
 pub struct StampSelectorWidgetPlugin;
 impl Plugin for StampSelectorWidgetPlugin {
     fn build(&self, app: &mut App) {
-        app.add_systems(Startup, init)
-            .add_systems(Update, update_wrapper);
+        app.add_systems(Startup, init).add_systems(
+            Update,
+            (
+                refresh_stamp_selector
+                    .run_if(resource_changed::<Stamps>.or(on_event::<AssetEvent<Stamp>>)),
+                update_wrapper,
+            )
+                .chain(),
+        );
         // .add_observer(change_stamp_img);
     }
 }
@@ -30,18 +38,27 @@ fn init(
 #[derive(Default, Debug, Component)]
 struct StampWidget {
     name: String,
+    cost: u32,
+    /// Where the entry is listed. Entries stay put while this and the cost do.
+    category: String,
     selected: bool,
 }
 
+#[derive(Default, Debug, Component)]
+struct StampLabel;
+
+/// The scrolling list of stamps, refilled whenever the catalog changes.
+#[derive(Default, Debug, Component)]
+struct StampList;
+
 fn init_stamp_selector(
     In(entity): In<Entity>,
     mut commands: Commands,
     stamps: Res<Stamps>,
     mut nodes: Query<&mut Node>,
     stamp_assets: Res<Assets<Stamp>>,
+    gameplay: Res<SimGameplayState>,
 ) {
-    // #333
-    let border_color = Color::linear_rgb(3. / 16., 3. / 16., 3. / 16.);
     let wrapper = commands
         .spawn((
             Name::new("stamps"),
@@ -56,38 +73,57 @@ fn init_stamp_selector(
                 ..Default::default()
             },
             Scrollable,
-            BorderColor(border_color),
+            StampList,
+            BorderColor(BORDER_COLOR),
             BorderRadius::all(Val::Px(5.)),
         ))
         .id();
-    let stamps = &stamps.px32;
-    stamps.iter().for_each(|(name, stamp)| {
-        let stamp = stamp_assets.get(stamp).expect("stamp");
-        let image_node = (
-            Node {
-                display: Display::Flex,
-                justify_content: JustifyContent::Center,
-                align_items: AlignItems::Center,
-                ..Default::default()
-            },
-            Pickable::IGNORE,
-            children![(
-                ImageNode {
-                    image: stamp.texture.clone(),
-                    texture_atlas: Some(stamp.atlas.clone()),
-                    ..Default::default()
-                },
-                Pickable::IGNORE,
-            )],
-        );
-        let text_node = |text| {
-            (
-                Pickable::IGNORE,
-                Text::new(text),
-                TextFont::from_font_size(16.),
-                Label,
-            )
-        };
+    spawn_stamp_entries(&mut commands, wrapper, &stamps, &stamp_assets, &gameplay);
+    let mut node = nodes.get_mut(entity).expect("node");
+    node.overflow = Overflow::scroll_y();
+    let mut parent = commands.entity(entity);
+    parent.add_child(wrapper);
+}
+
+/// Displayed size of the longer side of a stamp.
+const ICON_SIZE: f32 = 32.;
+// #333
+const BORDER_COLOR: Color = Color::linear_rgb(3. / 16., 3. / 16., 3. / 16.);
+
+/// One entry per stamp, grouped by category and sorted by cost.
+fn spawn_stamp_entries(
+    commands: &mut Commands,
+    wrapper: Entity,
+    stamps: &Stamps,
+    stamp_assets: &Assets<Stamp>,
+    gameplay: &SimGameplayState,
+) {
+    let entries = stamps
+        .catalog
+        .iter()
+        // Pattern files may still be loading.
+        .filter_map(|(name, handle)| Some((name, stamp_assets.get(handle)?)))
+        .sorted_by(|(a_name, a), (b_name, b)| {
+            (&a.category, a.cost, a_name).cmp(&(&b.category, b.cost, b_name))
+        })
+        .collect_vec();
+    let mut category = None;
+    for (name, stamp) in entries {
+        if category != Some(&stamp.category) {
+            category = Some(&stamp.category);
+            let header = commands
+                .spawn((
+                    Pickable::IGNORE,
+                    Text::new(stamp.category.clone()),
+                    TextFont::from_font_size(14.),
+                    Node {
+                        margin: UiRect::vertical(Val::Px(4.)),
+                        ..Default::default()
+                    },
+                ))
+                .id();
+            commands.entity(wrapper).add_child(header);
+        }
         let child_id = commands
             .spawn((
                 Node {
@@ -101,34 +137,158 @@ fn init_stamp_selector(
                     ..Default::default()
                 },
                 BackgroundColor(Color::linear_rgb(0.3, 0.3, 0.3)),
-                BorderColor(border_color),
+                BorderColor(BORDER_COLOR),
                 BorderRadius::all(Val::Px(5.)),
                 StampWidget {
                     name: name.to_owned(),
-                    selected: false,
+                    cost: stamp.cost,
+                    category: stamp.category.clone(),
+                    selected: gameplay.current_stamp.as_ref() == Some(name),
                 },
                 Pickable {
                     should_block_lower: true,
                     is_hoverable: true,
                 },
                 RelativeCursorPosition::default(),
-                children![image_node, text_node(name.clone(),)],
+                entry_content(name, stamp),
             ))
             .observe(wrapper_click)
             .id();
         commands.entity(wrapper).add_child(child_id);
-    });
-    let mut node = nodes.get_mut(entity).expect("node");
-    node.overflow = Overflow::scroll_y();
-    let mut parent = commands.entity(entity);
-    parent.add_child(wrapper);
+    }
+}
+
+/// Icon, name, cost and description of one entry. Owns everything it
+/// needs, so it can be inserted with commands.
+fn entry_content(name: &str, stamp: &Stamp) -> impl Bundle + use<> {
+    // Stamps come in any size; fit the longer side to the same icon size.
+    let icon = stamp.size.as_vec2() * ICON_SIZE / stamp.size.max_element().max(1) as f32;
+    let image_node = (
+        Node {
+            display: Display::Flex,
+            justify_content: JustifyContent::Center,
+            align_items: AlignItems::Center,
+            width: Val::Px(ICON_SIZE),
+            ..Default::default()
+        },
+        Pickable::IGNORE,
+        children![(
+            Node {
+                width: Val::Px(icon.x),
+                height: Val::Px(icon.y),
+                ..Default::default()
+            },
+            ImageNode {
+                image: stamp.texture.clone(),
+                texture_atlas: Some(stamp.atlas.clone()),
+                ..Default::default()
+            },
+            Pickable::IGNORE,
+        )],
+    );
+    let text_node = (
+        Node {
+            display: Display::Flex,
+            flex_direction: FlexDirection::Column,
+            ..Default::default()
+        },
+        Pickable::IGNORE,
+        children![
+            (
+                Pickable::IGNORE,
+                Text::new(format!("{name} ({})", stamp.cost)),
+                TextFont::from_font_size(16.),
+                Label,
+                StampLabel,
+            ),
+            (
+                Pickable::IGNORE,
+                Text::new(stamp.description.clone()),
+                TextFont::from_font_size(10.),
+                TextColor(Color::linear_rgb(0.6, 0.6, 0.6)),
+                Label,
+            ),
+        ],
+    );
+    children![image_node, text_node]
+}
+
+/// Picks up catalog edits and pattern files which finished loading.
+/// A stamp edited in place only has its own entry redrawn. The list is
+/// refilled when stamps come or go, or when one moves to another place in it.
+fn refresh_stamp_selector(
+    mut commands: Commands,
+    mut events: EventReader<AssetEvent<Stamp>>,
+    lists: Query<Entity, With<StampList>>,
+    entries: Query<(Entity, &StampWidget)>,
+    stamps: Res<Stamps>,
+    stamp_assets: Res<Assets<Stamp>>,
+    gameplay: Res<SimGameplayState>,
+) {
+    let mut refill = stamps.is_changed();
+    let mut redraw = vec![];
+    for event in events.read() {
+        let (AssetEvent::LoadedWithDependencies { id } | AssetEvent::Modified { id }) = event
+        else {
+            continue;
+        };
+        let Some(name) = stamps
+            .catalog
+            .iter()
+            .find_map(|(name, handle)| (handle.id() == *id).then_some(name))
+        else {
+            continue;
+        };
+        let Some(stamp) = stamp_assets.get(*id) else {
+            continue;
+        };
+        match entries.iter().find(|(_, widget)| widget.name == *name) {
+            Some((entry, widget))
+                if widget.category == stamp.category && widget.cost == stamp.cost =>
+            {
+                redraw.push((entry, name, stamp));
+            }
+            _ => refill = true,
+        }
+    }
+    if !refill {
+        for (entry, name, stamp) in redraw {
+            commands
+                .entity(entry)
+                .despawn_related::<Children>()
+                .insert(entry_content(name, stamp));
+        }
+        return;
+    }
+    for list in &lists {
+        commands.entity(list).despawn_related::<Children>();
+        spawn_stamp_entries(&mut commands, list, &stamps, &stamp_assets, &gameplay);
+    }
 }
 
-fn update_wrapper(mut query: Query<(&StampWidget, &mut BackgroundColor, &RelativeCursorPosition)>) {
+fn update_wrapper(
+    mut query: Query<(&StampWidget, &mut BackgroundColor, &RelativeCursorPosition)>,
+    mut labels: Query<(&ChildOf, &mut TextColor), With<StampLabel>>,
+    gameplay: Res<SimGameplayState>,
+) {
+    // Stamps the current player can't place are greyed out.
+    labels.iter_mut().for_each(|(parent, mut color)| {
+        let Ok((widget, ..)) = query.get(parent.parent()) else {
+            return;
+        };
+        let value = if gameplay.can_place(&widget.name, widget.cost, 1) {
+            Color::WHITE
+        } else {
+            Color::linear_rgb(0.3, 0.3, 0.3)
+        };
+        color.set_if_neq(TextColor(value));
+    });
     query
         .iter_mut()
         .for_each(|(widget, mut bg_color, cursor_pos)| {
-            if widget.selected {
+            if !gameplay.can_place(&widget.name, widget.cost, 1) {
+                *bg_color = BackgroundColor(Color::linear_rgb(0.05, 0.05, 0.05));
+            } else if widget.selected {
                 *bg_color = BackgroundColor(Color::linear_rgb(0.3, 0.3, 0.3));
             } else if cursor_pos.mouse_over() {
                 *bg_color = BackgroundColor(Color::linear_rgb(0.2, 0.2, 0.2));
@@ -142,6 +302,12 @@ fn wrapper_click(
     mut wrappers: Query<&mut StampWidget>,
     mut sim_state: ResMut<SimGameplayState>,
 ) {
+    if wrappers
+        .get(event.target)
+        .is_ok_and(|widget| !sim_state.can_place(&widget.name, widget.cost, 1))
+    {
+        return;
+    }
     wrappers
         .iter_mut()
         .for_each(|mut wrapper| wrapper.selected = false);
@@ -0,0 +1,93 @@
+//! Live territory counts, read from [`TerritoryStats`].
+
+use bevy::prelude::*;
+use bevy_hui::prelude::HtmlFunctions;
+
+use crate::sim::{SimSettings, TeamID, TerritoryStats};
+
+pub struct TerritoryStatsWidgetPlugin;
+impl Plugin for TerritoryStatsWidgetPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Startup, init).add_systems(
+            Update,
+            // New rows are filled right away, even on a paused board.
+            update_territory_stats
+                .run_if(resource_changed::<TerritoryStats>.or(any_match_filter::<Added<StatRow>>)),
+        );
+    }
+}
+
+fn init(mut funcs: HtmlFunctions) {
+    funcs.register("init_territory_stats", init_territory_stats);
+}
+
+#[derive(Component, Debug, Copy, Clone)]
+enum StatRow {
+    Empty,
+    Active,
+    Dying,
+    Dormant,
+    Team(TeamID),
+}
+
+fn init_territory_stats(
+    In(entity): In<Entity>,
+    mut commands: Commands,
+    settings: Res<SimSettings>,
+) {
+    let rows = [
+        StatRow::Empty,
+        StatRow::Active,
+        StatRow::Dying,
+        StatRow::Dormant,
+    ]
+    .into_iter()
+    .chain(settings.teams.iter().map(|team| StatRow::Team(team.id)));
+    for row in rows {
+        let child = commands
+            .spawn((
+                Text::default(),
+                TextFont::from_font_size(12.),
+                TextColor::WHITE,
+                row,
+            ))
+            .id();
+        commands.entity(entity).add_child(child);
+    }
+}
+
+fn update_territory_stats(
+    stats: Res<TerritoryStats>,
+    settings: Res<SimSettings>,
+    mut rows: Query<(&StatRow, &mut Text, &mut TextColor)>,
+) {
+    let count = &stats.current;
+    for (row, mut text, mut color) in &mut rows {
+        let value = match row {
+            StatRow::Empty => format!("Empty: {}", count.empty),
+            StatRow::Active => format!("Active: {}", count.active),
+            StatRow::Dying => format!("Dying: {}", count.dying),
+            StatRow::Dormant => format!("Dormant: {}", count.dormant),
+            StatRow::Team(team) => {
+                let Some(team_data) = settings.teams.get(*team) else {
+                    continue;
+                };
+                let [r, g, b, a] = team_data.color;
+                color.set_if_neq(TextColor(Color::srgba_u8(r, g, b, a)));
+                let cells = count.owned.get(*team).copied().unwrap_or(0);
+                let change = stats
+                    .last_change(*team)
+                    .map(|change| format!(" ({change:+})"))
+                    .unwrap_or_default();
+                format!(
+                    "{}: {cells} ({:.1}%){change}",
+                    team_data.name,
+                    count.share(cells) * 100.
+                )
+            }
+        };
+        if text.0 != value {
+            text.0 = value;
+        }
+    }
+}
@@ -0,0 +1,127 @@
+use bevy::{
+    input::{
+        ButtonState,
+        keyboard::{Key, KeyboardInput},
+    },
+    prelude::*,
+};
+use bevy_hui::prelude::*;
+
+use super::data::*;
+use crate::ui::data::TemplateHandles;
+
+/// # Text Input Widget
+///
+/// A button targeting the text node which displays the value.
+/// Pressing the button focuses it, Enter releases focus.
+///
+pub struct TextInputWidgetPlugin;
+impl Plugin for TextInputWidgetPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<TextInput>();
+        app.register_type::<TextInputChangedEvent>();
+        app.add_event::<TextInputChangedEvent>();
+        app.add_systems(Startup, setup);
+        app.add_systems(
+            Update,
+            (
+                focus,
+                type_text,
+                update_text.run_if(on_event::<TextInputChangedEvent>),
+            )
+                .chain(),
+        );
+    }
+}
+
+fn setup(
+    mut html_funcs: HtmlFunctions,
+    mut html_comps: HtmlComponents,
+    mut handles: ResMut<TemplateHandles>,
+    server: Res<AssetServer>,
+) {
+    let handle = server.load("hui/widgets/text_input.xml");
+    html_comps.register("text_input", handle.clone());
+    handles.insert("text_input", handle);
+    html_funcs.register("init_text_input", init_text_input);
+}
+
+fn init_text_input(
+    In(entity): In<Entity>,
+    mut cmd: Commands,
+    targets: Query<&UiTarget>,
+    texts: Query<&Text>,
+) {
+    let value = targets
+        .get(entity)
+        .ok()
+        .and_then(|target| texts.get(**target).ok())
+        .map(|text| text.0.trim().to_owned())
+        .unwrap_or_default();
+    cmd.entity(entity).insert(TextInput {
+        value,
+        focused: false,
+    });
+}
+
+fn focus(mut inputs: Query<(&Interaction, &mut TextInput)>, mouse: Res<ButtonInput<MouseButton>>) {
+    if !mouse.just_pressed(MouseButton::Left) {
+        return;
+    }
+    for (interaction, mut input) in &mut inputs {
+        input.focused = matches!(interaction, Interaction::Pressed);
+    }
+}
+
+fn type_text(
+    mut commands: Commands,
+    mut keys: EventReader<KeyboardInput>,
+    mut events: EventWriter<TextInputChangedEvent>,
+    mut inputs: Query<(Entity, &mut TextInput)>,
+) {
+    let Some((entity, mut input)) = inputs.iter_mut().find(|(_, input)| input.focused) else {
+        keys.clear();
+        return;
+    };
+    for key in keys.read() {
+        if key.state != ButtonState::Pressed {
+            continue;
+        }
+        match (&key.logical_key, &key.text) {
+            (Key::Enter, _) => {
+                input.focused = false;
+                continue;
+            }
+            (Key::Backspace, _) => {
+                input.value.pop();
+            }
+            (_, Some(text)) => {
+                input.value.extend(text.chars().filter(|c| !c.is_control()));
+            }
+            _ => continue,
+        }
+        let event = TextInputChangedEvent {
+            input: entity,
+            value: input.value.clone(),
+        };
+        events.write(event.clone());
+        commands.trigger(event);
+    }
+}
+
+fn update_text(
+    mut events: EventReader<TextInputChangedEvent>,
+    targets: Query<&UiTarget>,
+    mut texts: Query<&mut Text>,
+) {
+    for event in events.read() {
+        let Some(mut text) = targets
+            .get(event.input)
+            .ok()
+            .and_then(|target| texts.get_mut(**target).ok())
+        else {
+            continue;
+        };
+        text.0 = event.value.clone();
+    }
+}